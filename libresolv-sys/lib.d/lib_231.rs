@@ -26,9 +26,11 @@ pub use resolv::{
     __ns_sect_ns_s_ar,
     __ns_sect_ns_s_ns,
     __ns_sect_ns_s_qd,
+    __res_nclose as res_nclose,
     __res_ninit as res_ninit,
     __res_nquery as res_nquery,
     __res_nsearch as res_nsearch,
+    __res_nsend as res_nsend,
     __res_state,
     ns_initparse,
     ns_msg,
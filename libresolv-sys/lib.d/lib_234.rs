@@ -26,6 +26,7 @@ pub use resolv::{
     __ns_sect_ns_s_ar,
     __ns_sect_ns_s_ns,
     __ns_sect_ns_s_qd,
+    __res_nclose as res_nclose,
     __res_ninit as res_ninit,
     __res_state,
     ns_initparse,
@@ -36,6 +37,7 @@ pub use resolv::{
     ns_sect,
     res_nquery,
     res_nsearch,
+    res_nsend,
 };
 
 /// Options for the Resolver
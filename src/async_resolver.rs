@@ -0,0 +1,154 @@
+//! An asynchronous interface for use under Tokio (requires the `tokio` feature).
+//!
+//! libresolv only offers blocking calls, so queries run on Tokio's blocking threads, each
+//! using a resolver from a `ResolverPool`.  At most as many queries as the pool has
+//! resolvers are dispatched at once; further queries wait their turn without occupying a
+//! blocking thread.
+
+use crate::error::Error;
+use crate::record::{Record, RecordData};
+use crate::{Class, RecordType, Resolver, ResolverConfig, ResolverPool, Response};
+use std::net::IpAddr;
+use std::panic;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// An asynchronous resolver.  Cloning it is cheap and gives another handle to the same
+/// pool of resolvers.
+///
+/// Dropping a query's future cancels it.  A query still waiting for a resolver is
+/// abandoned outright; one already sent completes on its blocking thread (libresolv cannot
+/// be interrupted) and its result is discarded.
+#[derive(Clone)]
+pub struct AsyncResolver {
+    pool: ResolverPool,
+    permits: Arc<Semaphore>,
+    timeout: Option<Duration>,
+}
+
+impl AsyncResolver {
+    /// Create an asynchronous resolver using up to `size` resolvers configured from
+    /// `/etc/resolv.conf`
+    pub fn new(size: usize) -> AsyncResolver {
+        AsyncResolver::from_pool(ResolverPool::new(size))
+    }
+
+    /// Create an asynchronous resolver using up to `size` resolvers configured from `config`
+    pub fn from_config(config: ResolverConfig, size: usize) -> AsyncResolver {
+        AsyncResolver::from_pool(ResolverPool::from_config(config, size))
+    }
+
+    /// Create an asynchronous resolver using the resolvers of an existing pool
+    pub fn from_pool(pool: ResolverPool) -> AsyncResolver {
+        AsyncResolver {
+            permits: Arc::new(Semaphore::new(pool.size())),
+            pool,
+            timeout: None,
+        }
+    }
+
+    /// Get a handle to the same resolvers whose queries time out after `timeout`, including
+    /// any time spent waiting for a free resolver.  A query that times out returns
+    /// `Error::Timeout`.
+    pub fn with_timeout(&self, timeout: Duration) -> AsyncResolver {
+        AsyncResolver {
+            timeout: Some(timeout),
+            ..self.clone()
+        }
+    }
+
+    /// Lookup the record, applying the search algorithm.  See `Resolver::search()`.
+    pub async fn search(
+        &self,
+        name: &[u8],
+        class: Class,
+        typ: RecordType,
+    ) -> Result<Response, Error> {
+        let name = name.to_vec();
+        self.run(move |resolver| resolver.search(&name, class, typ))
+            .await
+    }
+
+    /// Lookup the record without the search algorithm.  See `Resolver::query()`.
+    pub async fn query(
+        &self,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+    ) -> Result<Response, Error> {
+        let dname = dname.to_vec();
+        self.run(move |resolver| resolver.query(&dname, class, typ))
+            .await
+    }
+
+    /// Query for records of type `T` in class IN and return the answers of that type
+    pub async fn lookup<T>(&self, dname: &[u8]) -> Result<Vec<Record<T>>, Error>
+    where
+        T: RecordData + Send + 'static,
+    {
+        let dname = dname.to_vec();
+        self.run(move |resolver| {
+            let response = resolver.query(&dname, Class::IN, T::get_record_type())?;
+            let answers: Vec<Record<T>> = response.answers::<T>().collect();
+            Ok(answers)
+        })
+        .await
+    }
+
+    /// Look up the IPv4 and IPv6 addresses of a name, concurrently.  Fails only if both
+    /// lookups fail.
+    pub async fn lookup_ip(&self, dname: &[u8]) -> Result<Vec<IpAddr>, Error> {
+        use crate::record::{A, AAAA};
+
+        let (v4, v6) = tokio::join!(self.lookup::<A>(dname), self.lookup::<AAAA>(dname));
+        let mut addresses: Vec<IpAddr> = Vec::new();
+        let v4_error = match v4 {
+            Ok(records) => {
+                addresses.extend(records.into_iter().map(|r| IpAddr::V4(r.data.address)));
+                None
+            }
+            Err(e) => Some(e),
+        };
+        match (v6, v4_error) {
+            (Ok(records), _) => {
+                addresses.extend(records.into_iter().map(|r| IpAddr::V6(r.data.address)));
+            }
+            (Err(e), Some(_)) => return Err(e),
+            (Err(_), None) => {}
+        }
+        Ok(addresses)
+    }
+
+    async fn run<F, R>(&self, work: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut Resolver) -> Result<R, Error> + Send + 'static,
+        R: Send + 'static,
+    {
+        let dispatch = async {
+            let permit = match self.permits.clone().acquire_owned().await {
+                Ok(p) => p,
+                Err(_) => return Err(Error::Cancelled),
+            };
+            let pool = self.pool.clone();
+            let task = tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                let mut resolver = pool.get().ok_or(Error::Init)?;
+                work(&mut resolver)
+            });
+            match task.await {
+                Ok(result) => result,
+                Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+                Err(_) => Err(Error::Cancelled),
+            }
+        };
+
+        match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, dispatch).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::Timeout),
+            },
+            None => dispatch.await,
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use super::Backend;
+use crate::config;
+use crate::cookie::Cookies;
+use crate::edns::{self, Edns, EdnsOption};
+use crate::error::{Error, ResolutionError};
+use crate::transport::{random_id, search_with, Transport};
+use crate::wire::{self, Header};
+use crate::{Class, RecordType, ResolverConfig, ResolverOption};
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::os::raw::c_char;
+use std::ptr;
+
+type Context = libresolv_sys::__res_state;
+
+// Large enough for any message, so that EDNS(0) responses are not cut short
+const BUFFER_LEN: usize = 65535;
+
+/// glibc's libresolv, using the thread-safe `res_n*` functions.
+///
+/// libresolv cannot add EDNS options to its queries, so when there are any, or cookies
+/// are in use, queries are built by this crate and sent with `res_nsend`, and the search
+/// list is applied by this crate too.  libresolv does not say which server answered, so
+/// with cookies the query is sent through this crate's `Transport` instead, to the same
+/// servers, each with its own cookies.
+pub struct Glibc {
+    // Boxed, as the context holds pointers into itself (the search list).
+    context: Box<Context>,
+    edns_options: Vec<EdnsOption>,
+    cookies: Option<Cookies>,
+}
+
+// The context only points into itself and at memory it owns, so it may move between
+// threads; it is never shared, as every use takes `&mut self`.
+unsafe impl Send for Glibc {}
+
+impl Drop for Glibc {
+    fn drop(&mut self) {
+        unsafe { libresolv_sys::res_nclose(&mut *self.context) };
+    }
+}
+
+impl Glibc {
+    /// Initialise from `config`, or from `/etc/resolv.conf` if there is none.  At most
+    /// three name servers are used, and if none are given the local host is queried.
+    pub fn new(config: Option<&ResolverConfig>) -> Option<Glibc> {
+        let mut glibc = Glibc {
+            context: Box::default(),
+            edns_options: Vec::new(),
+            cookies: None,
+        };
+        if unsafe { libresolv_sys::res_ninit(&mut *glibc.context) } != 0 {
+            return None;
+        }
+        if let Some(config) = config {
+            glibc.apply_config(config)?;
+        }
+        glibc.set_option(ResolverOption::Default, true);
+        Some(glibc)
+    }
+
+    fn apply_config(&mut self, config: &ResolverConfig) -> Option<()> {
+        let loopback = [SocketAddr::from(([127, 0, 0, 1], config::NAMESERVER_PORT))];
+        let nameservers = if config.nameservers.is_empty() {
+            &loopback[..]
+        } else {
+            &config.nameservers[..]
+        };
+        self.set_nameservers(nameservers)?;
+        self.set_search(&config.search);
+
+        let ctx = &mut *self.context;
+        let count = config.sortlist.len().min(ctx.sort_list.len());
+        for (slot, entry) in ctx.sort_list.iter_mut().zip(&config.sortlist) {
+            slot.addr.s_addr = u32::from(entry.address).to_be();
+            slot.mask = u32::from(entry.netmask).to_be();
+        }
+        ctx.set_nsort(count as _);
+        ctx.set_ndots(config.ndots.min(config::MAX_NDOTS) as _);
+        ctx.retrans = config.timeout as _;
+        ctx.retry = config.attempts as _;
+
+        ctx.options = (ResolverOption::Init as u64) | (ResolverOption::Default as u64);
+        for option in &config.options {
+            self.set_option(*option, true);
+        }
+
+        Some(())
+    }
+
+    // glibc keeps IPv4 servers in `nsaddr_list`.  IPv6 servers live in malloc'd
+    // `_u._ext.nsaddrs` slots (freed by res_nclose) with the `nsaddr_list` family zeroed.
+    fn set_nameservers(&mut self, nameservers: &[SocketAddr]) -> Option<()> {
+        let ctx = &mut *self.context;
+        let count = nameservers.len().min(ctx.nsaddr_list.len());
+        let mut count6 = 0;
+        for (i, addr) in nameservers.iter().take(count).enumerate() {
+            match *addr {
+                SocketAddr::V4(ref v4) => {
+                    let slot = &mut ctx.nsaddr_list[i];
+                    slot.sin_family = libc::AF_INET as _;
+                    slot.sin_port = v4.port().to_be();
+                    slot.sin_addr.s_addr = u32::from(*v4.ip()).to_be();
+                }
+                SocketAddr::V6(ref v6) => unsafe {
+                    ctx.nsaddr_list[i].sin_family = 0;
+                    let mut sa = ctx._u._ext.nsaddrs[i] as *mut libc::sockaddr_in6;
+                    if sa.is_null() {
+                        sa = libc::malloc(mem::size_of::<libc::sockaddr_in6>()) as *mut _;
+                        if sa.is_null() {
+                            return None;
+                        }
+                    }
+                    let mut sin6: libc::sockaddr_in6 = mem::zeroed();
+                    sin6.sin6_family = libc::AF_INET6 as _;
+                    sin6.sin6_port = v6.port().to_be();
+                    sin6.sin6_addr.s6_addr = v6.ip().octets();
+                    sin6.sin6_scope_id = v6.scope_id();
+                    ptr::write(sa, sin6);
+                    ctx._u._ext.nsaddrs[i] = sa as *mut _;
+                    count6 += 1;
+                },
+            }
+        }
+        ctx.nscount = count as _;
+        // Forces res_send to resynchronise its copy of the server list
+        ctx._u._ext.nscount = 0;
+        ctx._u._ext.nscount6 = count6;
+        Some(())
+    }
+
+    // The search list is stored NUL-separated in `defdname`, with `dnsrch` pointing at
+    // each domain, just as res_ninit leaves it.
+    fn set_search(&mut self, search: &[String]) {
+        let ctx = &mut *self.context;
+        for p in ctx.dnsrch.iter_mut() {
+            *p = ptr::null_mut();
+        }
+        for c in ctx.defdname.iter_mut() {
+            *c = 0;
+        }
+        let max_domains = ctx.dnsrch.len() - 1;
+        let mut offset = 0;
+        for (i, domain) in search.iter().take(max_domains).enumerate() {
+            let bytes = domain.as_bytes();
+            if bytes.contains(&0) || offset + bytes.len() + 1 > ctx.defdname.len() {
+                break;
+            }
+            for (j, b) in bytes.iter().enumerate() {
+                ctx.defdname[offset + j] = *b as c_char;
+            }
+            ctx.dnsrch[i] = ctx.defdname[offset..].as_mut_ptr();
+            offset += bytes.len() + 1;
+        }
+    }
+
+    fn has_option(&self, option: ResolverOption) -> bool {
+        self.context.options & option as u64 != 0
+    }
+
+    fn builds_queries(&self) -> bool {
+        !self.edns_options.is_empty() || self.cookies.is_some()
+    }
+
+    // The name servers, as set_nameservers() or res_ninit left them
+    fn nameservers(&self) -> Vec<SocketAddr> {
+        let ctx = &*self.context;
+        let count = (ctx.nscount.max(0) as usize).min(ctx.nsaddr_list.len());
+        (0..count)
+            .filter_map(|i| {
+                let v4 = &ctx.nsaddr_list[i];
+                if v4.sin_family == libc::AF_INET as _ {
+                    let ip = Ipv4Addr::from(u32::from_be(v4.sin_addr.s_addr));
+                    return Some(SocketAddr::from((ip, u16::from_be(v4.sin_port))));
+                }
+                let v6 = unsafe { ctx._u._ext.nsaddrs[i] } as *const libc::sockaddr_in6;
+                if v6.is_null() {
+                    return None;
+                }
+                let v6 = unsafe { &*v6 };
+                let ip = Ipv6Addr::from(v6.sin6_addr.s6_addr);
+                let port = u16::from_be(v6.sin6_port);
+                Some(SocketAddrV6::new(ip, port, 0, v6.sin6_scope_id).into())
+            })
+            .collect()
+    }
+
+    // This crate's transport, configured as libresolv is
+    fn transport(&self) -> Transport {
+        let ctx = &*self.context;
+        let mut config = ResolverConfig::new()
+            .timeout(ctx.retrans.max(0) as u32)
+            .attempts(ctx.retry.max(0) as u32);
+        config.nameservers = self.nameservers();
+        for option in [
+            ResolverOption::Rotate,
+            ResolverOption::UseVC,
+            ResolverOption::IgnTc,
+        ] {
+            config = config.option(option, self.has_option(option));
+        }
+        Transport::from_config(&config)
+    }
+
+    // The search list, as res_ninit or set_search() left it
+    fn search_list(&self) -> Vec<String> {
+        self.context
+            .dnsrch
+            .iter()
+            .take_while(|p| !p.is_null())
+            .map(|&p| unsafe { CStr::from_ptr(p) }.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    // Build the query and send it with res_nsend, interpreting the answer as res_nquery
+    // would
+    fn send_query(
+        &mut self,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+    ) -> Result<Vec<u8>, Error> {
+        let mut flags = 0;
+        if self.has_option(ResolverOption::Recurse) {
+            flags |= wire::FLAG_RD;
+        }
+        if self.has_option(ResolverOption::TrustAD) {
+            flags |= wire::FLAG_AD;
+        }
+        let edns = Edns::for_query(
+            &self.edns_options,
+            self.has_option(ResolverOption::UseEDNS0),
+            self.has_option(ResolverOption::UseDNSSEC),
+        );
+        let query = wire::build_query(
+            random_id(),
+            flags,
+            dname,
+            class as u16,
+            typ as u16,
+            edns.as_ref(),
+        )?;
+        let buffer = match self.cookies.take() {
+            Some(cookies) => {
+                let mut transport = self.transport();
+                transport.set_cookies(Some(cookies));
+                let result = transport.send(&query);
+                self.cookies = transport.cookies().cloned();
+                result?
+            }
+            None => self.send(&query)?,
+        };
+        match wire::check_rcode(&buffer) {
+            Err(e) if !wire::is_negative(&e) => Err(edns::with_extended_errors(e, &buffer)),
+            _ => Ok(buffer),
+        }
+    }
+
+    fn send(&mut self, query: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut buffer: Vec<u8> = vec![0; BUFFER_LEN];
+        let rlen: i32 = unsafe {
+            libresolv_sys::res_nsend(
+                &mut *self.context,
+                query.as_ptr(),
+                query.len() as i32,
+                buffer.as_mut_ptr(),
+                BUFFER_LEN as i32,
+            )
+        };
+        if rlen < 0 {
+            return Err(Error::Resolver(ResolutionError::TryAgain));
+        }
+        buffer.truncate(rlen as usize);
+        Ok(buffer)
+    }
+
+    fn get_error(&self) -> ResolutionError {
+        match self.context.res_h_errno {
+            0 => ResolutionError::Success,
+            1 => ResolutionError::HostNotFound,
+            2 => ResolutionError::TryAgain,
+            3 => ResolutionError::NoRecovery,
+            4 => ResolutionError::NoData,
+            _ => ResolutionError::HostNotFound, // fallback
+        }
+    }
+
+    // Keep the part of the buffer libresolv filled in.  For NXDOMAIN and NODATA,
+    // libresolv returns -1 but leaves the answer in the buffer; its length is found when
+    // the response is parsed.  The same goes for the last SERVFAIL, REFUSED, etc., whose
+    // extended errors are made part of the error.
+    fn finish(&self, mut buffer: Vec<u8>, rlen: i32) -> Result<Vec<u8>, Error> {
+        if rlen == -1 {
+            let error = Error::from(self.get_error());
+            if !holds_answer(&buffer, &error) {
+                return Err(error);
+            }
+            if wire::is_negative(&error) {
+                return Ok(buffer);
+            }
+            return Err(edns::with_extended_errors(error, &buffer));
+        }
+        // A response longer than the buffer was truncated to fit.
+        buffer.truncate(rlen as usize);
+        Ok(buffer)
+    }
+}
+
+// Whether the buffer holds a response giving this error, and not leftovers
+fn holds_answer(buffer: &[u8], error: &Error) -> bool {
+    match Header::parse(buffer) {
+        Ok(header) => {
+            header.flags & wire::FLAG_QR != 0 && wire::check_rcode(buffer).as_ref() == Err(error)
+        }
+        Err(_) => false,
+    }
+}
+
+impl Backend for Glibc {
+    fn search(&mut self, name: &[u8], class: Class, typ: RecordType) -> Result<Vec<u8>, Error> {
+        if self.builds_queries() {
+            let search = self.search_list();
+            let ndots = self.context.ndots() as u8;
+            return search_with(name, ndots, &search, |candidate| {
+                self.send_query(candidate, class, typ)
+            });
+        }
+        let name = match CString::new(name) {
+            Ok(c) => c,
+            Err(n) => return Err(Error::CString(n)),
+        };
+        let mut buffer: Vec<u8> = vec![0; BUFFER_LEN];
+
+        let rlen: i32 = unsafe {
+            libresolv_sys::res_nsearch(
+                &mut *self.context,
+                name.as_ptr(),
+                class as i32,
+                typ as i32,
+                buffer.as_mut_ptr(),
+                BUFFER_LEN as i32,
+            )
+        };
+        self.finish(buffer, rlen)
+    }
+
+    fn query(&mut self, dname: &[u8], class: Class, typ: RecordType) -> Result<Vec<u8>, Error> {
+        if self.builds_queries() {
+            return self.send_query(dname, class, typ);
+        }
+        let name = match CString::new(dname) {
+            Ok(c) => c,
+            Err(n) => return Err(Error::CString(n)),
+        };
+        let mut buffer: Vec<u8> = vec![0; BUFFER_LEN];
+
+        let rlen: i32 = unsafe {
+            libresolv_sys::res_nquery(
+                &mut *self.context,
+                name.as_ptr(),
+                class as i32,
+                typ as i32,
+                buffer.as_mut_ptr(),
+                BUFFER_LEN as i32,
+            )
+        };
+        self.finish(buffer, rlen)
+    }
+
+    fn set_option(&mut self, option: ResolverOption, value: bool) {
+        if value {
+            self.context.options |= option as u64;
+        } else {
+            self.context.options &= !(option as u64);
+        }
+    }
+
+    fn has_option(&self, option: ResolverOption) -> bool {
+        Glibc::has_option(self, option)
+    }
+
+    fn set_edns_options(&mut self, options: &[EdnsOption]) {
+        self.edns_options = options.to_vec();
+    }
+
+    fn set_cookies(&mut self, cookies: Option<Cookies>) {
+        self.cookies = cookies;
+    }
+}
@@ -0,0 +1,85 @@
+//! The ways a `Resolver` can send its queries.
+//!
+//! With the `glibc` feature (the default), queries go through glibc's `libresolv`, as they
+//! always have.  With the `native` feature, they are sent by this crate's own
+//! `Transport`, which needs no C library.  If both are enabled, glibc is the default and
+//! the native backend can be chosen with `Resolver::with_backend()`.  Responses are parsed
+//! by this crate in either case, so `Response` and `Record` behave identically.
+
+use crate::cookie::Cookies;
+use crate::edns::EdnsOption;
+use crate::error::Error;
+use crate::{Class, RecordType, ResolverConfig, ResolverOption};
+
+#[cfg(not(any(feature = "glibc", feature = "native")))]
+compile_error!("at least one of the `glibc` and `native` features must be enabled");
+
+#[cfg(feature = "glibc")]
+mod glibc;
+#[cfg(feature = "glibc")]
+pub use self::glibc::Glibc;
+
+#[cfg(feature = "native")]
+mod native;
+
+/// Something that can resolve queries, returning complete response messages.
+///
+/// Answers saying that the name does not exist (NXDOMAIN) or has no data of the type
+/// asked for (NODATA) are returned like any other, so that their authority section can be
+/// seen; the `Resolver` turns them into errors.  Other failures are reported the way
+/// `res_nquery` reports them.
+pub trait Backend: Send {
+    /// Lookup the record, applying the search algorithm
+    fn search(&mut self, name: &[u8], class: Class, typ: RecordType) -> Result<Vec<u8>, Error>;
+
+    /// Lookup the record without applying the search algorithm
+    fn query(&mut self, dname: &[u8], class: Class, typ: RecordType) -> Result<Vec<u8>, Error>;
+
+    /// Set or unset an option
+    fn set_option(&mut self, option: ResolverOption, value: bool);
+
+    /// Whether an option is set
+    fn has_option(&self, option: ResolverOption) -> bool;
+
+    /// Send these EDNS options with each query
+    fn set_edns_options(&mut self, options: &[EdnsOption]);
+
+    /// Send DNS cookies made with `cookies`, or none
+    fn set_cookies(&mut self, cookies: Option<Cookies>);
+}
+
+/// The available backends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// glibc's libresolv
+    #[cfg(feature = "glibc")]
+    Glibc,
+    /// This crate's own UDP/TCP transport
+    #[cfg(feature = "native")]
+    Native,
+}
+
+impl Default for BackendKind {
+    #[cfg(feature = "glibc")]
+    fn default() -> BackendKind {
+        BackendKind::Glibc
+    }
+
+    #[cfg(not(feature = "glibc"))]
+    fn default() -> BackendKind {
+        BackendKind::Native
+    }
+}
+
+impl BackendKind {
+    /// Create a backend of this kind from `config`, or from the system configuration if
+    /// there is none.
+    pub(crate) fn create(self, config: Option<&ResolverConfig>) -> Option<Box<dyn Backend>> {
+        match self {
+            #[cfg(feature = "glibc")]
+            BackendKind::Glibc => Some(Box::new(Glibc::new(config)?)),
+            #[cfg(feature = "native")]
+            BackendKind::Native => Some(Box::new(native::create(config))),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use super::Backend;
+use crate::cookie::Cookies;
+use crate::edns::EdnsOption;
+use crate::error::Error;
+use crate::{Class, RecordType, ResolverConfig, ResolverOption, Transport};
+
+// Without glibc, the system configuration is read by this crate.  If there is none, the
+// defaults apply (querying the local host), as they would for glibc.
+pub fn create(config: Option<&ResolverConfig>) -> Transport {
+    match config {
+        Some(config) => Transport::from_config(config),
+        None => {
+            let config = ResolverConfig::system()
+                .unwrap_or_else(|_| ResolverConfig::new().with_environment());
+            Transport::from_config(&config)
+        }
+    }
+}
+
+impl Backend for Transport {
+    fn search(&mut self, name: &[u8], class: Class, typ: RecordType) -> Result<Vec<u8>, Error> {
+        self.search_answer(name, class, typ)
+    }
+
+    fn query(&mut self, dname: &[u8], class: Class, typ: RecordType) -> Result<Vec<u8>, Error> {
+        self.query_answer(dname, class, typ)
+    }
+
+    fn set_option(&mut self, option: ResolverOption, value: bool) {
+        self.option(option, value)
+    }
+
+    fn has_option(&self, option: ResolverOption) -> bool {
+        Transport::has_option(self, option)
+    }
+
+    fn set_edns_options(&mut self, options: &[EdnsOption]) {
+        Transport::set_edns_options(self, options.to_vec())
+    }
+
+    fn set_cookies(&mut self, cookies: Option<Cookies>) {
+        Transport::set_cookies(self, cookies)
+    }
+}
@@ -0,0 +1,530 @@
+//! A cache of answers in front of a `Resolver`.
+//!
+//! Answers are cached by question (name, type and class) for as long as the records in
+//! them live: the smallest TTL in the answer section.  An answer for a client subnet
+//! (RFC 7871) is cached for the network its scope prefix covers, and reused only for
+//! clients in that network; an answer with scope zero, or without a client subnet, is
+//! reused for everyone.  Answers that the name does not
+//! exist (NXDOMAIN) or has no data of the type asked for (NODATA) are cached as RFC 2308
+//! describes, for the lesser of the TTL and MINIMUM field of the SOA record in the
+//! authority section; without one they are not cached.  Other failures are never cached.
+//!
+//! Optionally, expired answers are kept for a while longer and served if the name
+//! servers cannot be reached (RFC 8767), and answers in frequent use are refreshed in
+//! the background shortly before they expire.
+
+use crate::edns::{self, ClientSubnet};
+use crate::error::Error;
+use crate::record::{Record, SOA};
+use crate::wire;
+use crate::{Class, RecordType, Resolver, Response, Section};
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default number of answers kept
+pub const DEFAULT_CAPACITY: usize = 4096;
+/// Default longest time an answer is kept, in seconds (one day)
+pub const DEFAULT_MAX_TTL: u32 = 86400;
+/// Default longest time a negative answer is kept, in seconds (three hours, the upper end
+/// of the range RFC 2308 recommends)
+pub const DEFAULT_MAX_NEGATIVE_TTL: u32 = 10800;
+/// TTL given to records in stale answers, as RFC 8767 recommends
+pub const STALE_TTL: u32 = 30;
+/// How long to serve a stale answer without asking the name servers again, after they
+/// failed to refresh it (RFC 8767's failure recheck timer)
+pub const STALE_RECHECK: Duration = Duration::from_secs(30);
+
+/// A source of the current time, so that expiry can be tested without waiting
+pub trait Clock: Send {
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Counts of cache lookups, since the cache was created or the counts were reset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Queries answered from the cache, including negative answers
+    pub hits: u64,
+    /// Queries answered from the cache with a negative answer
+    pub negative_hits: u64,
+    /// Queries passed on to the resolver
+    pub misses: u64,
+    /// Answers discarded to make room for others before they expired
+    pub evictions: u64,
+    /// Queries answered with an expired answer because the name servers failed
+    pub stale_hits: u64,
+    /// Answers refreshed in the background before they expired
+    pub prefetches: u64,
+}
+
+// Name in lower-case wire format, type, class, and the network (address and prefix
+// length) the answer is limited to, if any
+type Key = (Vec<u8>, u16, u16, Option<(IpAddr, u8)>);
+
+enum Answer {
+    Positive(Vec<u8>),
+    Negative(Error),
+}
+
+struct Entry {
+    answer: Answer,
+    stored: Instant,
+    expires: Instant,
+    // TTL the entry was stored with, after clamping
+    ttl: u32,
+    // Position in the recently-used order
+    tick: u64,
+    // Times served from the cache
+    hits: u32,
+    prefetching: bool,
+    // Serve this entry stale without asking again until then
+    recheck: Option<Instant>,
+}
+
+enum Lookup {
+    Fresh(Result<Response, Error>),
+    Stale,
+    Miss,
+}
+
+// A thread with its own resolver, refreshing answers on request
+struct Prefetcher {
+    requests: Sender<(Key, Class, RecordType)>,
+    results: Receiver<(Key, Result<Response, Error>)>,
+}
+
+impl Prefetcher {
+    fn start(mut resolver: Resolver) -> Prefetcher {
+        let (requests, queue) = mpsc::channel::<(Key, Class, RecordType)>();
+        let (done, results) = mpsc::channel();
+        // Ends when the cache, and with it the sender, is dropped
+        thread::spawn(move || {
+            for (key, class, typ) in queue {
+                let (labels, _) = wire::read_name(&key.0, 0).unwrap();
+                let mut name = wire::name_to_string(&labels).into_bytes();
+                if name != b"." {
+                    name.push(b'.');
+                }
+                let subnet = key_subnet(&key);
+                let result = resolver.query_response_from(&name, class, typ, subnet.as_ref());
+                if done.send((key, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        Prefetcher { requests, results }
+    }
+}
+
+/// A `Resolver` that caches its answers.  See the module documentation for what is cached
+/// and for how long.
+pub struct CachingResolver {
+    resolver: Resolver,
+    clock: Box<dyn Clock>,
+    capacity: usize,
+    min_ttl: u32,
+    max_ttl: u32,
+    max_negative_ttl: u32,
+    max_stale: u32,
+    // Entries served at least this often are prefetched; 0 disables prefetching
+    prefetch_hits: u32,
+    prefetcher: Option<Prefetcher>,
+    entries: HashMap<Key, Entry>,
+    // Keys by when they were last used, least recent first
+    recent: BTreeMap<u64, Key>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl CachingResolver {
+    /// Cache the answers of `resolver`, with the default limits
+    pub fn new(resolver: Resolver) -> CachingResolver {
+        CachingResolver {
+            resolver,
+            clock: Box::new(SystemClock),
+            capacity: DEFAULT_CAPACITY,
+            min_ttl: 0,
+            max_ttl: DEFAULT_MAX_TTL,
+            max_negative_ttl: DEFAULT_MAX_NEGATIVE_TTL,
+            max_stale: 0,
+            prefetch_hits: 0,
+            prefetcher: None,
+            entries: HashMap::new(),
+            recent: BTreeMap::new(),
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Keep at most `capacity` answers, discarding the least recently used
+    pub fn capacity(mut self, capacity: usize) -> CachingResolver {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Keep answers for at least `ttl` seconds, even if their records say otherwise
+    pub fn min_ttl(mut self, ttl: u32) -> CachingResolver {
+        self.min_ttl = ttl;
+        self
+    }
+
+    /// Keep answers for at most `ttl` seconds
+    pub fn max_ttl(mut self, ttl: u32) -> CachingResolver {
+        self.max_ttl = ttl;
+        self
+    }
+
+    /// Keep negative answers for at most `ttl` seconds
+    pub fn max_negative_ttl(mut self, ttl: u32) -> CachingResolver {
+        self.max_negative_ttl = ttl;
+        self
+    }
+
+    /// Serve answers up to `max_stale` seconds after they expire when the name servers
+    /// fail to answer (with an error other than NXDOMAIN or NODATA).  Records in stale
+    /// answers have a TTL of `STALE_TTL`.  RFC 8767 suggests one to three days.
+    pub fn serve_stale(mut self, max_stale: u32) -> CachingResolver {
+        self.max_stale = max_stale;
+        self
+    }
+
+    /// Refresh answers served from the cache at least `min_hits` times, in the
+    /// background, once less than a tenth of their TTL remains.  The refreshed answer is
+    /// picked up by a later query.  Zero, the default, disables prefetching.
+    pub fn prefetch(mut self, min_hits: u32) -> CachingResolver {
+        self.prefetch_hits = min_hits;
+        self
+    }
+
+    /// Tell the time with `clock` instead of the system clock
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> CachingResolver {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// The underlying resolver, e.g. to set options.  Answers already cached are kept.
+    pub fn resolver(&mut self) -> &mut Resolver {
+        &mut self.resolver
+    }
+
+    /// Number of answers cached, including any that have expired but not yet been
+    /// discarded
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// Discard every cached answer
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recent.clear();
+    }
+
+    /// Lookup the record, from the cache if possible.  See `Resolver::query()`.  The TTLs
+    /// of records from the cache are reduced by the time they have been cached.
+    pub fn query(
+        &mut self,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+    ) -> Result<Response, Error> {
+        let subnet = self.resolver.client_subnet().cloned();
+        self.cached_query(dname, class, typ, subnet)
+    }
+
+    /// Lookup the record on behalf of clients in `subnet`, from the cache if possible.
+    /// See `Resolver::query_with_subnet()`.
+    pub fn query_with_subnet(
+        &mut self,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+        subnet: &ClientSubnet,
+    ) -> Result<Response, Error> {
+        self.cached_query(dname, class, typ, Some(subnet.clone()))
+    }
+
+    fn cached_query(
+        &mut self,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+        subnet: Option<ClientSubnet>,
+    ) -> Result<Response, Error> {
+        let question = match wire::encode_name(dname) {
+            Ok(name) => (name.to_ascii_lowercase(), typ as u16, class as u16, None),
+            // Let the resolver report the error
+            Err(_) => return self.resolver.query(dname, class, typ),
+        };
+        self.collect_prefetches();
+        let now = self.clock.now();
+        let key = self.find_key(&question, subnet.as_ref());
+        let stale = match self.lookup(&key, (class, typ), now) {
+            Lookup::Fresh(result) => return result,
+            Lookup::Stale => true,
+            Lookup::Miss => false,
+        };
+        if stale && self.entries[&key].recheck.is_some_and(|t| now < t) {
+            return self.serve_stale_entry(&key);
+        }
+
+        self.stats.misses += 1;
+        match self
+            .resolver
+            .query_response_from(dname, class, typ, subnet.as_ref())
+        {
+            Ok(response) => self.store(question, subnet.as_ref(), response, now),
+            Err(ref e) if stale && !wire::is_negative(e) => {
+                self.entries.get_mut(&key).unwrap().recheck = Some(now + STALE_RECHECK);
+                self.serve_stale_entry(&key)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn clamp(&self, ttl: u32, max: u32) -> u32 {
+        ttl.min(max).max(self.min_ttl)
+    }
+
+    // The key of the most specific entry for the question that covers the subnet, or of
+    // where an answer for everyone would be
+    fn find_key(&self, question: &Key, subnet: Option<&ClientSubnet>) -> Key {
+        if let Some(subnet) = subnet {
+            for prefix in (1..=subnet.source_prefix).rev() {
+                let network = (edns::mask(subnet.address, prefix), prefix);
+                let key = (question.0.clone(), question.1, question.2, Some(network));
+                if self.entries.contains_key(&key) {
+                    return key;
+                }
+            }
+        }
+        question.clone()
+    }
+
+    // Cache a response from the resolver to a question sent with `subnet`, returning it
+    // as `Resolver::query()` would
+    fn store(
+        &mut self,
+        question: Key,
+        subnet: Option<&ClientSubnet>,
+        response: Response,
+        now: Instant,
+    ) -> Result<Response, Error> {
+        let key = scoped_key(question, subnet, &response);
+        match edns::check_rcode(response.as_bytes()) {
+            Ok(()) => {
+                let ttl = answer_ttl(&response).map(|ttl| self.clamp(ttl, self.max_ttl));
+                if let Some(ttl) = ttl {
+                    let answer = Answer::Positive(response.as_bytes().to_vec());
+                    self.insert(key, answer, now, ttl);
+                }
+                Ok(response)
+            }
+            Err(error) => {
+                let ttl = negative_ttl(&response).map(|ttl| self.clamp(ttl, self.max_negative_ttl));
+                if let Some(ttl) = ttl {
+                    self.insert(key, Answer::Negative(error.clone()), now, ttl);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    fn lookup(&mut self, key: &Key, question: (Class, RecordType), now: Instant) -> Lookup {
+        let (expires, tick) = match self.entries.get(key) {
+            Some(entry) => (entry.expires, entry.tick),
+            None => return Lookup::Miss,
+        };
+        if expires <= now {
+            let stale_until = expires + Duration::from_secs(u64::from(self.max_stale));
+            if now < stale_until {
+                return Lookup::Stale;
+            }
+            self.recent.remove(&tick);
+            self.entries.remove(key);
+            return Lookup::Miss;
+        }
+
+        self.touch(key);
+        let entry = self.entries.get_mut(key).unwrap();
+        entry.hits = entry.hits.saturating_add(1);
+        self.stats.hits += 1;
+
+        let remaining = expires - now;
+        let hot = self.prefetch_hits > 0 && entry.hits >= self.prefetch_hits;
+        if hot && !entry.prefetching && remaining * 10 < Duration::from_secs(u64::from(entry.ttl)) {
+            entry.prefetching = true;
+            self.start_prefetch(key.clone(), question);
+        }
+
+        let entry = &self.entries[key];
+        match entry.answer {
+            Answer::Positive(ref message) => {
+                let elapsed = now.duration_since(entry.stored).as_secs();
+                let elapsed = elapsed.min(u64::from(u32::MAX)) as u32;
+                Lookup::Fresh(cached_response(message, |ttl| ttl.saturating_sub(elapsed)))
+            }
+            Answer::Negative(ref error) => {
+                self.stats.negative_hits += 1;
+                Lookup::Fresh(Err(error.clone()))
+            }
+        }
+    }
+
+    fn serve_stale_entry(&mut self, key: &Key) -> Result<Response, Error> {
+        self.touch(key);
+        self.stats.stale_hits += 1;
+        match self.entries[key].answer {
+            Answer::Positive(ref message) => cached_response(message, |_| STALE_TTL),
+            Answer::Negative(ref error) => Err(error.clone()),
+        }
+    }
+
+    // Mark an entry as the most recently used
+    fn touch(&mut self, key: &Key) {
+        let entry = self.entries.get_mut(key).unwrap();
+        self.recent.remove(&entry.tick);
+        self.tick += 1;
+        entry.tick = self.tick;
+        self.recent.insert(self.tick, key.clone());
+    }
+
+    fn start_prefetch(&mut self, key: Key, (class, typ): (Class, RecordType)) {
+        if self.prefetcher.is_none() {
+            self.prefetcher = self.resolver.duplicate().map(Prefetcher::start);
+        }
+        let sent = match self.prefetcher {
+            Some(ref prefetcher) => prefetcher.requests.send((key.clone(), class, typ)).is_ok(),
+            None => false,
+        };
+        if sent {
+            self.stats.prefetches += 1;
+        } else if let Some(entry) = self.entries.get_mut(&key) {
+            // Try again on the next hit
+            entry.prefetching = false;
+        }
+    }
+
+    fn collect_prefetches(&mut self) {
+        let results: Vec<_> = match self.prefetcher {
+            Some(ref prefetcher) => prefetcher.results.try_iter().collect(),
+            None => return,
+        };
+        let now = self.clock.now();
+        for (key, result) in results {
+            match result {
+                Ok(response) => {
+                    let subnet = key_subnet(&key);
+                    let question = (key.0.clone(), key.1, key.2, None);
+                    let _ = self.store(question, subnet.as_ref(), response, now);
+                }
+                Err(_) => {
+                    if let Some(entry) = self.entries.get_mut(&key) {
+                        entry.prefetching = false;
+                    }
+                }
+            }
+        }
+    }
+
+    fn insert(&mut self, key: Key, answer: Answer, now: Instant, ttl: u32) {
+        if ttl == 0 {
+            return;
+        }
+        if let Some(old) = self.entries.remove(&key) {
+            self.recent.remove(&old.tick);
+        }
+        while self.entries.len() >= self.capacity {
+            let oldest = match self.recent.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            let evicted = self.recent.remove(&oldest).unwrap();
+            self.entries.remove(&evicted);
+            self.stats.evictions += 1;
+        }
+
+        self.tick += 1;
+        self.recent.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                answer,
+                stored: now,
+                expires: now + Duration::from_secs(u64::from(ttl)),
+                ttl,
+                tick: self.tick,
+                hits: 0,
+                prefetching: false,
+                recheck: None,
+            },
+        );
+    }
+}
+
+// The key to cache a response under: limited to the network of the scope prefix the
+// server gave, but no more specific than the subnet asked about (RFC 7871 section 7.3.1)
+fn scoped_key(question: Key, subnet: Option<&ClientSubnet>, response: &Response) -> Key {
+    let (subnet, scope) = match (subnet, response.client_subnet()) {
+        (Some(subnet), Ok(Some(answered))) => (subnet, answered.scope_prefix),
+        _ => return question,
+    };
+    let prefix = scope.min(subnet.source_prefix);
+    if prefix == 0 {
+        return question;
+    }
+    let network = (edns::mask(subnet.address, prefix), prefix);
+    (question.0, question.1, question.2, Some(network))
+}
+
+// The subnet to refresh an entry for
+fn key_subnet(key: &Key) -> Option<ClientSubnet> {
+    key.3
+        .map(|(address, prefix)| ClientSubnet::new(address, prefix))
+}
+
+// Parse a cached message, adjusting the TTL of each record
+fn cached_response<F: Fn(u32) -> u32>(message: &[u8], ttl: F) -> Result<Response, Error> {
+    let mut response = Response::from_buffer(message.to_vec())?;
+    response.map_ttls(ttl);
+    Ok(response)
+}
+
+// The smallest TTL in the answer section, if it could be read
+fn answer_ttl(response: &Response) -> Option<u32> {
+    (0..response.get_section_count(Section::Answer))
+        .map(|i| response.get_rr(Section::Answer, i).map(|rr| rr.ttl))
+        .collect::<Result<Vec<u32>, Error>>()
+        .ok()?
+        .into_iter()
+        .min()
+}
+
+// RFC 2308 section 5: the lesser of the SOA record's TTL and its MINIMUM field
+fn negative_ttl(response: &Response) -> Option<u32> {
+    let soa: Record<SOA> = response.authorities::<SOA>().next()?;
+    Some(soa.ttl.min(soa.data.minimum))
+}
@@ -0,0 +1,140 @@
+//! Following CNAME and DNAME aliases to the records they lead to.
+//!
+//! `Response::answers()` returns every record of the type asked for, wherever it is in
+//! the answer section.  A `Resolution` instead starts from the name asked for and
+//! follows the aliases one at a time, keeping only the records owned by the name at the
+//! end of the chain.
+
+use crate::error::{Error, ResolutionError};
+use crate::record::{Record, RecordData, CNAME, DNAME};
+use crate::{wire, Class, Resolver, Response};
+use std::iter;
+use std::str;
+
+/// Most aliases followed before giving up
+pub const MAX_CHAIN_LEN: usize = 16;
+
+/// One step in a chain of aliases
+#[derive(Debug, Clone)]
+pub struct Alias {
+    /// The name that is an alias
+    pub name: String,
+    /// The name it is an alias for
+    pub target: String,
+    pub ttl: u32,
+    /// If the alias was synthesised from a DNAME record, that record's owner name
+    pub dname: Option<String>,
+}
+
+/// The records a name resolved to, and the aliases followed to get there
+#[derive(Debug, Clone)]
+pub struct Resolution<T: RecordData> {
+    /// The aliases followed, in order, starting from the name asked for
+    pub chain: Vec<Alias>,
+    /// The name at the end of the chain, which owns the records
+    pub name: String,
+    pub records: Vec<Record<T>>,
+}
+
+impl<T: RecordData> Resolution<T> {
+    /// Start resolving `name`, with no aliases followed yet
+    pub fn new(name: &str) -> Resolution<T> {
+        Resolution {
+            chain: Vec::new(),
+            name: name.to_owned(),
+            records: Vec::new(),
+        }
+    }
+
+    /// Follow aliases through the answer section of `response`, from the name reached so
+    /// far, and take the records of type `T` owned by the name at the end.  Returns whether
+    /// there were any; if not, `name` is where another query should continue.  A DNAME
+    /// record takes precedence over a CNAME synthesised from it.
+    pub fn follow(&mut self, response: &Response) -> Result<bool, Error> {
+        let cnames: Vec<Record<CNAME>> = response.answers().collect();
+        let dnames: Vec<Record<DNAME>> = response.answers().collect();
+        loop {
+            let here = wire::labels(self.name.as_bytes())?;
+            self.records = response
+                .answers::<T>()
+                .filter(|r| same_name(&r.name, &here))
+                .collect();
+            if !self.records.is_empty() {
+                return Ok(true);
+            }
+
+            let synthesized = dnames.iter().find_map(|d| {
+                let target = d.data.synthesize(&d.name, &self.name)?;
+                Some(Alias {
+                    name: self.name.clone(),
+                    target,
+                    ttl: d.ttl,
+                    dname: Some(d.name.clone()),
+                })
+            });
+            let alias = match synthesized {
+                Some(alias) => alias,
+                None => match cnames.iter().find(|c| same_name(&c.name, &here)) {
+                    Some(c) => Alias {
+                        name: self.name.clone(),
+                        target: c.data.cname.clone(),
+                        ttl: c.ttl,
+                        dname: None,
+                    },
+                    None => return Ok(false),
+                },
+            };
+            self.push(alias)?;
+        }
+    }
+
+    fn push(&mut self, alias: Alias) -> Result<(), Error> {
+        let target = wire::labels(alias.target.as_bytes())?;
+        let mut seen = self
+            .chain
+            .iter()
+            .map(|a| &a.name)
+            .chain(iter::once(&alias.name));
+        if seen.any(|name| same_name(name, &target)) {
+            return Err(Error::AliasLoop);
+        }
+        if self.chain.len() >= MAX_CHAIN_LEN {
+            return Err(Error::AliasChainTooLong);
+        }
+        self.name = alias.target.clone();
+        self.chain.push(alias);
+        Ok(())
+    }
+}
+
+fn same_name(name: &str, labels: &[Vec<u8>]) -> bool {
+    match wire::labels(name.as_bytes()) {
+        Ok(l) => wire::names_equal(&l, labels),
+        Err(_) => false,
+    }
+}
+
+impl Resolver {
+    /// Look up records of type `T`, following CNAME and DNAME aliases from `dname` and
+    /// querying again where an answer stops short of the end of the chain.  Fails with
+    /// `Error::AliasLoop` or `Error::AliasChainTooLong` if the chain does not end.
+    pub fn resolve<T: RecordData>(
+        &mut self,
+        dname: &[u8],
+        class: Class,
+    ) -> Result<Resolution<T>, Error> {
+        let mut resolution = Resolution::new(str::from_utf8(dname)?);
+        loop {
+            let name = resolution.name.clone();
+            let response = self.query(name.as_bytes(), class, T::get_record_type())?;
+            let followed = resolution.chain.len();
+            if resolution.follow(&response)? {
+                return Ok(resolution);
+            }
+            // The answer neither holds the records nor leads anywhere new.
+            if resolution.chain.len() == followed {
+                return Err(Error::Resolver(ResolutionError::NoData));
+            }
+        }
+    }
+}
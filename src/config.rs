@@ -0,0 +1,295 @@
+//! Resolver configuration in `resolv.conf(5)` syntax.
+//!
+//! A `ResolverConfig` can be parsed from any file or string, or built up in code, and then
+//! handed to `Resolver::from_config()`.  This lets a program use a private resolver
+//! configuration without touching the system-wide `/etc/resolv.conf`.
+
+use crate::error::Error;
+use crate::ResolverOption;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV6};
+use std::path::Path;
+use std::str::FromStr;
+
+/// The system resolver configuration file
+pub const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// The port name servers listen on unless configured otherwise
+pub const NAMESERVER_PORT: u16 = 53;
+
+/// Maximum value for `ndots` (RES_MAXNDOTS)
+pub const MAX_NDOTS: u8 = 15;
+/// Maximum value for `timeout`, in seconds (RES_MAXRETRANS)
+pub const MAX_TIMEOUT: u32 = 30;
+/// Maximum value for `attempts` (RES_MAXRETRY)
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// An entry in the `sortlist`: an address and the netmask to compare it under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortlistEntry {
+    pub address: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+}
+
+/// Resolver configuration, as found in `resolv.conf`.
+///
+/// Parsing is lenient in the same way glibc is: unknown keywords, unknown options and
+/// malformed values are ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolverConfig {
+    /// Name servers to query, in order
+    pub nameservers: Vec<SocketAddr>,
+    /// Domains to append to names with fewer than `ndots` dots
+    pub search: Vec<String>,
+    /// Preferred networks used to order addresses returned by gethostbyname
+    pub sortlist: Vec<SortlistEntry>,
+    /// Number of dots a name must have before it is first tried as absolute
+    pub ndots: u8,
+    /// Seconds to wait for a name server before trying the next one
+    pub timeout: u32,
+    /// Number of times to try each name server
+    pub attempts: u32,
+    /// Resolver options turned on by the `options` keyword
+    pub options: Vec<ResolverOption>,
+}
+
+impl Default for ResolverConfig {
+    fn default() -> ResolverConfig {
+        ResolverConfig {
+            nameservers: Vec::new(),
+            search: Vec::new(),
+            sortlist: Vec::new(),
+            ndots: 1,
+            timeout: 5,
+            attempts: 2,
+            options: Vec::new(),
+        }
+    }
+}
+
+impl ResolverConfig {
+    /// Create an empty configuration with glibc's default settings.  Use the builder
+    /// methods to fill it in.
+    pub fn new() -> ResolverConfig {
+        Default::default()
+    }
+
+    /// Read the system configuration from `/etc/resolv.conf`, honouring the `LOCALDOMAIN`
+    /// and `RES_OPTIONS` environment variables.
+    pub fn system() -> Result<ResolverConfig, Error> {
+        ResolverConfig::from_path(RESOLV_CONF)
+    }
+
+    /// Read a configuration file in `resolv.conf` syntax, honouring the `LOCALDOMAIN` and
+    /// `RES_OPTIONS` environment variables.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<ResolverConfig, Error> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ResolverConfig::parse(&contents).with_environment())
+    }
+
+    /// Parse a configuration in `resolv.conf` syntax.  The environment is not consulted;
+    /// see `with_environment()`.
+    pub fn parse(contents: &str) -> ResolverConfig {
+        let mut config = ResolverConfig::new();
+
+        for line in contents.lines() {
+            let line = match line.find(['#', ';']) {
+                Some(i) => &line[..i],
+                None => line,
+            };
+            let mut words = line.split_whitespace();
+            let keyword = match words.next() {
+                Some(k) => k,
+                None => continue,
+            };
+            match keyword {
+                "nameserver" => {
+                    if let Some(addr) = words.next().and_then(parse_nameserver) {
+                        config.nameservers.push(addr);
+                    }
+                }
+                // `domain` and `search` are mutually exclusive; the last one wins.
+                "domain" => {
+                    if let Some(domain) = words.next() {
+                        config.search = vec![domain.to_owned()];
+                    }
+                }
+                "search" => {
+                    config.search = words.map(|w| w.to_owned()).collect();
+                }
+                "sortlist" => {
+                    config.sortlist = words.filter_map(parse_sortlist_entry).collect();
+                }
+                "options" => {
+                    for word in words {
+                        config.apply_option(word);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        config
+    }
+
+    /// Apply the `LOCALDOMAIN` and `RES_OPTIONS` environment variables, as glibc does
+    /// after reading `resolv.conf`.  `LOCALDOMAIN` replaces the search list and
+    /// `RES_OPTIONS` is interpreted like an `options` line.
+    pub fn with_environment(mut self) -> ResolverConfig {
+        if let Ok(domains) = ::std::env::var("LOCALDOMAIN") {
+            self.search = domains.split_whitespace().map(|w| w.to_owned()).collect();
+        }
+        if let Ok(options) = ::std::env::var("RES_OPTIONS") {
+            for word in options.split_whitespace() {
+                self.apply_option(word);
+            }
+        }
+        self
+    }
+
+    /// Add a name server
+    pub fn nameserver(mut self, addr: SocketAddr) -> ResolverConfig {
+        self.nameservers.push(addr);
+        self
+    }
+
+    /// Add a domain to the search list
+    pub fn search_domain(mut self, domain: &str) -> ResolverConfig {
+        self.search.push(domain.to_owned());
+        self
+    }
+
+    /// Add an entry to the sortlist
+    pub fn sortlist_entry(mut self, address: Ipv4Addr, netmask: Ipv4Addr) -> ResolverConfig {
+        self.sortlist.push(SortlistEntry { address, netmask });
+        self
+    }
+
+    /// Set the `ndots` threshold (capped at 15)
+    pub fn ndots(mut self, ndots: u8) -> ResolverConfig {
+        self.ndots = ndots.min(MAX_NDOTS);
+        self
+    }
+
+    /// Set the per-server timeout in seconds (capped at 30)
+    pub fn timeout(mut self, timeout: u32) -> ResolverConfig {
+        self.timeout = timeout.min(MAX_TIMEOUT);
+        self
+    }
+
+    /// Set the number of attempts per server (capped at 5)
+    pub fn attempts(mut self, attempts: u32) -> ResolverConfig {
+        self.attempts = attempts.min(MAX_ATTEMPTS);
+        self
+    }
+
+    /// Set or unset an option
+    pub fn option(mut self, option: ResolverOption, value: bool) -> ResolverConfig {
+        self.options.retain(|o| *o != option);
+        if value {
+            self.options.push(option);
+        }
+        self
+    }
+
+    /// Whether an option is turned on
+    pub fn has_option(&self, option: ResolverOption) -> bool {
+        self.options.contains(&option)
+    }
+
+    fn apply_option(&mut self, word: &str) {
+        let (name, value) = match word.find(':') {
+            Some(i) => (&word[..i], Some(&word[i + 1..])),
+            None => (word, None),
+        };
+        let number = value.and_then(|v| u32::from_str(v).ok());
+        match (name, number) {
+            ("ndots", Some(n)) => self.ndots = n.min(MAX_NDOTS as u32) as u8,
+            ("timeout", Some(n)) => self.timeout = n.clamp(1, MAX_TIMEOUT),
+            ("attempts", Some(n)) => self.attempts = n.clamp(1, MAX_ATTEMPTS),
+            ("rotate", _) => self.set_option(ResolverOption::Rotate),
+            ("edns0", _) => self.set_option(ResolverOption::UseEDNS0),
+            ("trust-ad", _) => self.set_option(ResolverOption::TrustAD),
+            ("use-vc", _) => self.set_option(ResolverOption::UseVC),
+            ("no-reload", _) => self.set_option(ResolverOption::NoReload),
+            ("no-tld-query", _) => self.set_option(ResolverOption::NoTLDQuery),
+            ("single-request", _) => self.set_option(ResolverOption::SngLkup),
+            ("single-request-reopen", _) => self.set_option(ResolverOption::SngLkupReop),
+            ("debug", _) => self.set_option(ResolverOption::Debug),
+            _ => {}
+        }
+    }
+
+    fn set_option(&mut self, option: ResolverOption) {
+        if !self.options.contains(&option) {
+            self.options.push(option);
+        }
+    }
+}
+
+impl FromStr for ResolverConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ResolverConfig, Error> {
+        Ok(ResolverConfig::parse(s))
+    }
+}
+
+fn parse_nameserver(word: &str) -> Option<SocketAddr> {
+    // IPv6 addresses may carry a zone ("fe80::1%eth0"), the interface to reach them
+    // through.  An entry whose interface is unknown is ignored.
+    let (address, zone) = match word.find('%') {
+        Some(i) => (&word[..i], Some(&word[i + 1..])),
+        None => (word, None),
+    };
+    match (IpAddr::from_str(address).ok()?, zone) {
+        (ip, None) => Some(SocketAddr::new(ip, NAMESERVER_PORT)),
+        (IpAddr::V6(ip), Some(zone)) => {
+            let scope_id = interface_index(zone)?;
+            Some(SocketAddrV6::new(ip, NAMESERVER_PORT, 0, scope_id).into())
+        }
+        (IpAddr::V4(_), Some(_)) => None,
+    }
+}
+
+// The index of the interface a zone names, either by number or (with glibc) by name
+fn interface_index(zone: &str) -> Option<u32> {
+    if let Ok(index) = u32::from_str(zone) {
+        return Some(index);
+    }
+    #[cfg(feature = "glibc")]
+    {
+        let name = std::ffi::CString::new(zone).ok()?;
+        match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+            0 => None,
+            index => Some(index),
+        }
+    }
+    #[cfg(not(feature = "glibc"))]
+    None
+}
+
+fn parse_sortlist_entry(word: &str) -> Option<SortlistEntry> {
+    let (address, netmask) = match word.find('/') {
+        Some(i) => (&word[..i], Some(&word[i + 1..])),
+        None => (word, None),
+    };
+    let address = Ipv4Addr::from_str(address).ok()?;
+    let netmask = match netmask {
+        Some(m) => Ipv4Addr::from_str(m).ok()?,
+        None => classful_netmask(address),
+    };
+    Some(SortlistEntry { address, netmask })
+}
+
+// glibc falls back to the historical class A/B/C netmask when none is given.
+fn classful_netmask(address: Ipv4Addr) -> Ipv4Addr {
+    let first = address.octets()[0];
+    if first < 128 {
+        Ipv4Addr::new(255, 0, 0, 0)
+    } else if first < 192 {
+        Ipv4Addr::new(255, 255, 0, 0)
+    } else {
+        Ipv4Addr::new(255, 255, 255, 0)
+    }
+}
@@ -0,0 +1,142 @@
+//! DNS cookies (RFC 7873), for resistance to spoofed responses.
+//!
+//! The client cookie sent to each name server is a hash of a secret and the server's
+//! address (as RFC 9018 suggests), so that servers cannot link the client's queries to
+//! one another.  Server cookies are remembered per name server address and sent back with
+//! later queries.  A response carrying a cookie that is not ours is ignored as spoofed,
+//! as is one without a cookie from a server that has given us one.  A BADCOOKIE response
+//! is retried with the server cookie it gives, over TCP if the server still refuses.
+//!
+//! The secret is replaced, and the server cookies bound to it forgotten, after a
+//! configurable interval or on request.
+
+use crate::edns::{Cookie, EdnsOption, OPTION_COOKIE};
+use crate::transport::random_u64;
+use crate::{Rcode, Response};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Times a query is sent to one server when it answers BADCOOKIE: once more with its
+/// server cookie, then over TCP
+pub(crate) const MAX_COOKIE_ATTEMPTS: u32 = 3;
+
+/// A client secret and the server cookies learnt with it
+#[derive(Debug, Clone)]
+pub struct Cookies {
+    secret: [u8; 16],
+    created: Instant,
+    rotation: Option<Duration>,
+    servers: HashMap<SocketAddr, Vec<u8>>,
+}
+
+/// What to do with a response, going by its cookie
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    Accept,
+    /// BADCOOKIE, and the server cookie it gave has been learnt
+    Retry,
+}
+
+impl Default for Cookies {
+    fn default() -> Cookies {
+        Cookies::new()
+    }
+}
+
+impl Cookies {
+    /// A new random secret, kept until `rotate()` is called
+    pub fn new() -> Cookies {
+        let mut secret = [0; 16];
+        secret[..8].copy_from_slice(&random_u64().to_be_bytes());
+        secret[8..].copy_from_slice(&random_u64().to_be_bytes());
+        Cookies {
+            secret,
+            created: Instant::now(),
+            rotation: None,
+            servers: HashMap::new(),
+        }
+    }
+
+    /// Replace the secret whenever it is older than `interval`
+    pub fn rotate_every(mut self, interval: Duration) -> Cookies {
+        self.rotation = Some(interval);
+        self
+    }
+
+    /// Replace the secret now, forgetting the server cookies
+    pub fn rotate(&mut self) {
+        let rotation = self.rotation;
+        *self = Cookies::new();
+        self.rotation = rotation;
+    }
+
+    /// The client cookie for `server`
+    pub fn client_cookie(&mut self, server: SocketAddr) -> [u8; 8] {
+        if self.rotation.is_some_and(|r| self.created.elapsed() >= r) {
+            self.rotate();
+        }
+        self.client_cookie_now(server)
+    }
+
+    /// The server cookie `server` last gave, if any
+    pub fn server_cookie(&self, server: SocketAddr) -> Option<&[u8]> {
+        self.servers.get(&server).map(|c| &c[..])
+    }
+
+    fn client_cookie_now(&self, server: SocketAddr) -> [u8; 8] {
+        let mut hasher = DefaultHasher::new();
+        self.secret.hash(&mut hasher);
+        server.ip().hash(&mut hasher);
+        hasher.finish().to_be_bytes()
+    }
+
+    /// The option to send to `server`
+    pub(crate) fn option(&mut self, server: SocketAddr) -> EdnsOption {
+        EdnsOption::Cookie(Cookie {
+            client: self.client_cookie(server),
+            server: self.servers.get(&server).cloned().unwrap_or_default(),
+        })
+    }
+
+    /// Whether `answer` could be from `server`: it has our client cookie, or it has no
+    /// cookie and `server` has never given us one (RFC 7873 section 5.3)
+    pub(crate) fn matches(&self, server: SocketAddr, answer: &[u8]) -> bool {
+        match response_cookie(answer) {
+            Some(Some(cookie)) => cookie.client == self.client_cookie_now(server),
+            Some(None) => false,
+            None => !self.servers.contains_key(&server),
+        }
+    }
+
+    /// Learn the server cookie in a response that `matches()`
+    pub(crate) fn learn(&mut self, server: SocketAddr, answer: &[u8]) -> Verdict {
+        let cookie = match response_cookie(answer) {
+            Some(Some(cookie)) => cookie,
+            _ => return Verdict::Accept,
+        };
+        if !cookie.server.is_empty() {
+            self.servers.insert(server, cookie.server);
+        }
+        let badcookie = Response::from_buffer(answer.to_vec())
+            .map(|r| r.rcode() == Rcode::BadCookie)
+            .unwrap_or(false);
+        if badcookie {
+            Verdict::Retry
+        } else {
+            Verdict::Accept
+        }
+    }
+}
+
+// The cookie in a response: None if there is none, Some(None) if it is malformed
+fn response_cookie(answer: &[u8]) -> Option<Option<Cookie>> {
+    let edns = Response::from_buffer(answer.to_vec()).ok()?.edns().ok()??;
+    edns.options.into_iter().find_map(|o| match o {
+        EdnsOption::Cookie(cookie) => Some(Some(cookie)),
+        EdnsOption::Unknown(OPTION_COOKIE, _) => Some(None),
+        _ => None,
+    })
+}
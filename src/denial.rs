@@ -0,0 +1,295 @@
+//! Authenticated denial of existence: whether the NSEC or NSEC3 records of a response
+//! prove that a name, or records of a type at a name, do not exist (RFC 4035 section 5.4
+//! and RFC 5155 section 8).  Requires the `dnssec` feature, for NSEC3 hashing.
+//!
+//! Only the content of the records is considered; checking their signatures is
+//! `dnssec::Validator`'s business.  A response whose denial records have been stripped
+//! proves nothing, which is how it can be told apart from a real NXDOMAIN or NODATA.
+//!
+//! NSEC3 records with more than `dnssec::MAX_NSEC3_ITERATIONS` iterations prove nothing,
+//! so that a response cannot make the validator spend long hashing.
+//!
+//! Denial records at a delegation point (with NS but no SOA) or at a DNAME are not used
+//! for names below them, as the parent zone cannot speak for those.
+
+use crate::dnssec::{base32hex_decode, nsec3_hash, MAX_NSEC3_ITERATIONS, NSEC3_SHA1};
+use crate::error::Error;
+use crate::record::{Record, RecordType, NSEC, NSEC3};
+use crate::{wire, Response};
+use std::cmp::Ordering;
+
+/// What denial records prove about a name and type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denial {
+    /// Neither the name nor a wildcard that could match it exists
+    NxDomain,
+    /// The name, or the wildcard matching it, exists without records of the type
+    NoData,
+    /// The name falls in an NSEC3 opt-out span: there is no signed delegation to it,
+    /// though there may be an unsigned one
+    OptOut,
+    /// Nothing: the records are missing, do not apply, or contradict the denial
+    Unproven,
+}
+
+/// What the NSEC and NSEC3 records in the authority section of `response` prove about
+/// records of type `rr_type` at `name`
+pub fn prove(response: &Response, name: &str, rr_type: u16) -> Result<Denial, Error> {
+    let nsec: Vec<Record<NSEC>> = response.authorities().collect();
+    let nsec3: Vec<Record<NSEC3>> = response.authorities().collect();
+    prove_records(&nsec, &nsec3, name, rr_type)
+}
+
+/// As `prove()`, given the denial records
+pub fn prove_records(
+    nsec: &[Record<NSEC>],
+    nsec3: &[Record<NSEC3>],
+    name: &str,
+    rr_type: u16,
+) -> Result<Denial, Error> {
+    let name = wire::labels(name.as_bytes())?;
+    match prove_nsec(nsec, &name, rr_type)? {
+        Denial::Unproven => prove_nsec3(nsec3, &name, rr_type),
+        denial => Ok(denial),
+    }
+}
+
+/// Whether `nsec` or `nsec3` prove that no name closer to `name` than the wildcard its
+/// answer was expanded from exists, by covering the next closer name: the ancestor of
+/// `name` with one more label than `labels`, the RRSIG's count (RFC 4035 section 5.3.4
+/// and RFC 5155 section 8.8)
+pub fn proves_expansion(
+    nsec: &[Record<NSEC>],
+    nsec3: &[Record<NSEC3>],
+    name: &str,
+    labels: u8,
+) -> Result<bool, Error> {
+    let name = wire::labels(name.as_bytes())?;
+    let closer = usize::from(labels) + 1;
+    if closer > name.len() {
+        return Ok(false);
+    }
+    let next_closer = &name[name.len() - closer..];
+    if nsecs(nsec)?.iter().any(|n| n.covers(next_closer)) {
+        return Ok(true);
+    }
+    for n in nsec3s(nsec3, next_closer) {
+        if n.data.iterations > MAX_NSEC3_ITERATIONS {
+            continue;
+        }
+        let hash = nsec3_hash(
+            NSEC3_SHA1,
+            &wire::name_to_string(next_closer),
+            &n.data.salt,
+            n.data.iterations,
+        )?;
+        if n.covers(&hash) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Whether a name with types `has` proves there are no records of `rr_type` there
+fn lacks<F: Fn(u16) -> bool>(has: F, rr_type: u16) -> bool {
+    if has(rr_type) || has(RecordType::CNAME as u16) {
+        return false;
+    }
+    // The parent's record at a delegation speaks only for DS; the child's apex, not for
+    // DS.  Only a delegation lacks DS records (RFC 6840 section 4.4, RFC 5155 section 8.9).
+    if rr_type == RecordType::DS as u16 {
+        has(RecordType::NS as u16) && !has(RecordType::SOA as u16)
+    } else {
+        !has(RecordType::NS as u16) || has(RecordType::SOA as u16)
+    }
+}
+
+// Whether a record with types `has` may deny names below its owner
+fn speaks_below<F: Fn(u16) -> bool>(has: F) -> bool {
+    !has(RecordType::DNAME as u16) && (!has(RecordType::NS as u16) || has(RecordType::SOA as u16))
+}
+
+struct Nsec<'a> {
+    owner: Vec<Vec<u8>>,
+    next: Vec<Vec<u8>>,
+    data: &'a NSEC,
+}
+
+impl Nsec<'_> {
+    fn has(&self, rr_type: u16) -> bool {
+        self.data.has_type(rr_type)
+    }
+
+    fn covers(&self, name: &[Vec<u8>]) -> bool {
+        if name.len() > self.owner.len()
+            && wire::is_subdomain(name, &self.owner)
+            && !speaks_below(|t| self.has(t))
+        {
+            return false;
+        }
+        let after_owner = wire::canonical_cmp(&self.owner, name) == Ordering::Less;
+        let before_next = wire::canonical_cmp(name, &self.next) == Ordering::Less;
+        if wire::canonical_cmp(&self.owner, &self.next) == Ordering::Less {
+            after_owner && before_next
+        } else {
+            // The last NSEC of the zone, whose next name is the apex
+            after_owner || before_next
+        }
+    }
+}
+
+fn nsecs(records: &[Record<NSEC>]) -> Result<Vec<Nsec<'_>>, Error> {
+    records
+        .iter()
+        .map(|r| {
+            Ok(Nsec {
+                owner: wire::labels(r.name.as_bytes())?,
+                next: wire::labels(r.data.next_domain_name.as_bytes())?,
+                data: &r.data,
+            })
+        })
+        .collect()
+}
+
+fn prove_nsec(records: &[Record<NSEC>], name: &[Vec<u8>], rr_type: u16) -> Result<Denial, Error> {
+    let nsecs = nsecs(records)?;
+
+    if let Some(nsec) = nsecs.iter().find(|n| wire::names_equal(&n.owner, name)) {
+        return Ok(nodata(lacks(|t| nsec.has(t), rr_type)));
+    }
+    let cover = match nsecs.iter().find(|n| n.covers(name)) {
+        Some(cover) => cover,
+        None => return Ok(Denial::Unproven),
+    };
+    // The closest encloser is the longest ancestor of the name the covering NSEC shows
+    // to exist.
+    let common = common_suffix(name, &cover.owner).max(common_suffix(name, &cover.next));
+    let wildcard = wildcard_at(&name[name.len() - common..]);
+    if let Some(nsec) = nsecs
+        .iter()
+        .find(|n| wire::names_equal(&n.owner, &wildcard))
+    {
+        return Ok(nodata(lacks(|t| nsec.has(t), rr_type)));
+    }
+    Ok(if nsecs.iter().any(|n| n.covers(&wildcard)) {
+        Denial::NxDomain
+    } else {
+        Denial::Unproven
+    })
+}
+
+struct Nsec3<'a> {
+    owner_hash: Vec<u8>,
+    zone: Vec<Vec<u8>>,
+    data: &'a NSEC3,
+}
+
+impl Nsec3<'_> {
+    fn has(&self, rr_type: u16) -> bool {
+        self.data.has_type(rr_type)
+    }
+
+    fn covers(&self, hash: &[u8]) -> bool {
+        let (owner, next) = (&self.owner_hash[..], &self.data.next_hashed_owner[..]);
+        if owner < next {
+            owner < hash && hash < next
+        } else {
+            owner < hash || hash < next
+        }
+    }
+}
+
+// The NSEC3 records of zones `name` is in
+fn nsec3s<'a>(records: &'a [Record<NSEC3>], name: &[Vec<u8>]) -> Vec<Nsec3<'a>> {
+    records
+        .iter()
+        .filter(|r| r.data.hash_algorithm == NSEC3_SHA1)
+        .filter_map(|r| {
+            let labels = wire::labels(r.name.as_bytes()).ok()?;
+            let (first, zone) = labels.split_first()?;
+            Some(Nsec3 {
+                owner_hash: base32hex_decode(first)?,
+                zone: zone.to_vec(),
+                data: &r.data,
+            })
+        })
+        .filter(|n| wire::is_subdomain(name, &n.zone))
+        .collect()
+}
+
+fn prove_nsec3(records: &[Record<NSEC3>], name: &[Vec<u8>], rr_type: u16) -> Result<Denial, Error> {
+    let mut nsec3s = nsec3s(records, name);
+    // Only records of the innermost zone are used, with the parameters of one of them.
+    let (zone, salt, iterations) = match nsec3s.iter().max_by_key(|n| n.zone.len()) {
+        Some(n) => (n.zone.clone(), n.data.salt.clone(), n.data.iterations),
+        None => return Ok(Denial::Unproven),
+    };
+    if iterations > MAX_NSEC3_ITERATIONS {
+        return Ok(Denial::Unproven);
+    }
+    nsec3s.retain(|n| {
+        wire::names_equal(&n.zone, &zone) && n.data.salt == salt && n.data.iterations == iterations
+    });
+    let hash = |labels: &[Vec<u8>]| {
+        nsec3_hash(NSEC3_SHA1, &wire::name_to_string(labels), &salt, iterations)
+    };
+    let matching = |hash: &[u8]| nsec3s.iter().find(|n| n.owner_hash == hash);
+    let covering = |hash: &[u8]| nsec3s.iter().find(|n| n.covers(hash));
+
+    if let Some(nsec3) = matching(&hash(name)?) {
+        return Ok(nodata(lacks(|t| nsec3.has(t), rr_type)));
+    }
+
+    // The closest encloser proof: the longest ancestor with an NSEC3, and an NSEC3
+    // covering the name one label below it
+    let mut proof = None;
+    for i in 1..=name.len() - zone.len() {
+        let encloser = &name[i..];
+        if let Some(nsec3) = matching(&hash(encloser)?) {
+            if !speaks_below(|t| nsec3.has(t)) {
+                return Ok(Denial::Unproven);
+            }
+            proof = covering(&hash(&name[i - 1..])?).map(|cover| (encloser, cover));
+            break;
+        }
+    }
+    let (encloser, cover) = match proof {
+        Some(proof) => proof,
+        None => return Ok(Denial::Unproven),
+    };
+    if cover.data.is_opt_out() {
+        return Ok(Denial::OptOut);
+    }
+    let wildcard = hash(&wildcard_at(encloser))?;
+    if let Some(nsec3) = matching(&wildcard) {
+        return Ok(nodata(lacks(|t| nsec3.has(t), rr_type)));
+    }
+    Ok(if covering(&wildcard).is_some() {
+        Denial::NxDomain
+    } else {
+        Denial::Unproven
+    })
+}
+
+fn nodata(proved: bool) -> Denial {
+    if proved {
+        Denial::NoData
+    } else {
+        Denial::Unproven
+    }
+}
+
+// The number of labels two names share at the end
+fn common_suffix(a: &[Vec<u8>], b: &[Vec<u8>]) -> usize {
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| x.eq_ignore_ascii_case(y))
+        .count()
+}
+
+fn wildcard_at(encloser: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut wildcard = vec![b"*".to_vec()];
+    wildcard.extend_from_slice(encloser);
+    wildcard
+}
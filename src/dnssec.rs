@@ -0,0 +1,867 @@
+//! DNSSEC (RFC 4033, 4034 and 4035) computations and validation, using ring (requires
+//! the `dnssec` feature).
+//!
+//! A `Validator` labels each RRset of a response `Secure`, `Insecure`, `Bogus` or
+//! `Indeterminate` by walking the chain of trust down from its `TrustAnchors` (the root
+//! zone's key-signing keys unless others are given).  It fetches DNSKEY and DS records
+//! with the resolver as needed, and remembers the keys of each zone it has validated.
+//!
+//! A zone is insecure when its parent has no DS records for it, provided the parent
+//! signs the answer saying so, or when all of its DS records use algorithms or digest
+//! types not supported here.  Signatures are verified for RSA/SHA-256, ECDSA P-256 and
+//! P-384 with SHA-256 and SHA-384, and Ed25519.  The parent's NSEC or NSEC3 records
+//! must prove the DS records absent at a delegation; see the `denial` module.
+//!
+//! Zone cuts are found the same way, walking down from the trust anchor: each name on
+//! the way is a cut if its parent has signed DS records for it or proves it an unsigned
+//! delegation, and is not if the parent proves it no delegation.  SOA records are not
+//! trusted to place them.
+
+use crate::denial::{self, Denial};
+use crate::error::Error;
+use crate::record::{self, Record, RecordData, RecordType, Rr, DNSKEY, DS, NSEC, NSEC3, RRSIG};
+use crate::{wire, Class, Resolver, ResolverOption, Response, Section};
+use ring::{digest, signature};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// DS digest types
+pub const DIGEST_SHA1: u8 = 1;
+pub const DIGEST_SHA256: u8 = 2;
+pub const DIGEST_SHA384: u8 = 4;
+
+/// DNSSEC algorithms whose signatures can be verified
+pub const RSASHA256: u8 = 8;
+pub const ECDSAP256SHA256: u8 = 13;
+pub const ECDSAP384SHA384: u8 = 14;
+pub const ED25519: u8 = 15;
+
+/// Digest `data` as a DS record of type `digest_type` would
+pub fn digest(digest_type: u8, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let algorithm = match digest_type {
+        DIGEST_SHA1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        DIGEST_SHA256 => &digest::SHA256,
+        DIGEST_SHA384 => &digest::SHA384,
+        _ => return Err(Error::UnsupportedAlgorithm(digest_type)),
+    };
+    Ok(digest::digest(algorithm, data).as_ref().to_vec())
+}
+
+/// Whether signatures made with `algorithm` can be verified
+pub fn is_supported(algorithm: u8) -> bool {
+    matches!(
+        algorithm,
+        RSASHA256 | ECDSAP256SHA256 | ECDSAP384SHA384 | ED25519
+    )
+}
+
+/// Verify that `rrsig` is `key`'s signature over `data`, which is normally
+/// `RRSIG::signed_data()`.  Neither the validity period nor the key tag is checked.
+pub fn verify(key: &DNSKEY, rrsig: &RRSIG, data: &[u8]) -> Result<(), Error> {
+    if key.algorithm != rrsig.algorithm {
+        return Err(Error::BadSignature);
+    }
+    let verified = match key.algorithm {
+        RSASHA256 => {
+            let (e, n) = rsa_components(&key.public_key)?;
+            signature::RsaPublicKeyComponents { n, e }.verify(
+                &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
+                data,
+                &rrsig.signature,
+            )
+        }
+        ECDSAP256SHA256 | ECDSAP384SHA384 => {
+            let algorithm = if key.algorithm == ECDSAP256SHA256 {
+                &signature::ECDSA_P256_SHA256_FIXED
+            } else {
+                &signature::ECDSA_P384_SHA384_FIXED
+            };
+            // DNSKEY holds the bare point (RFC 6605); ring wants it uncompressed-tagged.
+            let mut point = vec![4];
+            point.extend_from_slice(&key.public_key);
+            signature::UnparsedPublicKey::new(algorithm, point).verify(data, &rrsig.signature)
+        }
+        ED25519 => signature::UnparsedPublicKey::new(&signature::ED25519, &key.public_key)
+            .verify(data, &rrsig.signature),
+        algorithm => return Err(Error::UnsupportedAlgorithm(algorithm)),
+    };
+    verified.map_err(|_| Error::BadSignature)
+}
+
+/// NSEC3 hash algorithms
+pub const NSEC3_SHA1: u8 = 1;
+
+/// The most NSEC3 iterations hashed (RFC 9276 section 3.2).  Proofs with more prove
+/// nothing, and a zone whose parent denies its DS records with them is insecure.
+pub const MAX_NSEC3_ITERATIONS: u16 = 150;
+
+/// The NSEC3 hash of `name` (RFC 5155 section 5): SHA-1 of its canonical form and `salt`,
+/// rehashed with the salt `iterations` more times.  Fails with
+/// `Error::TooManyIterations` for more than `MAX_NSEC3_ITERATIONS`.
+pub fn nsec3_hash(
+    algorithm: u8,
+    name: &str,
+    salt: &[u8],
+    iterations: u16,
+) -> Result<Vec<u8>, Error> {
+    if algorithm != NSEC3_SHA1 {
+        return Err(Error::UnsupportedAlgorithm(algorithm));
+    }
+    if iterations > MAX_NSEC3_ITERATIONS {
+        return Err(Error::TooManyIterations);
+    }
+    let mut hash = wire::canonical_name(name)?;
+    for _ in 0..=iterations {
+        let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+        context.update(&hash);
+        context.update(salt);
+        hash = context.finish().as_ref().to_vec();
+    }
+    Ok(hash)
+}
+
+const BASE32HEX: &[u8; 32] = b"0123456789abcdefghijklmnopqrstuv";
+
+/// Encode `data` in base32hex without padding, as NSEC3 owner names are (RFC 4648)
+pub fn base32hex(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let (mut bits, mut count) = (0u32, 0);
+    for &b in data {
+        bits = (bits << 8) | u32::from(b);
+        count += 8;
+        while count >= 5 {
+            count -= 5;
+            out.push(BASE32HEX[(bits >> count) as usize & 31] as char);
+        }
+    }
+    if count > 0 {
+        out.push(BASE32HEX[(bits << (5 - count)) as usize & 31] as char);
+    }
+    out
+}
+
+/// Decode base32hex, in either case, or `None` if it is not valid
+pub fn base32hex_decode(text: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 5 / 8);
+    let (mut bits, mut count) = (0u32, 0);
+    for &c in text {
+        let value = BASE32HEX
+            .iter()
+            .position(|&b| b == c.to_ascii_lowercase())?;
+        bits = (bits << 5) | value as u32;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Some(out)
+}
+
+// The exponent and modulus of an RSA key (RFC 3110 section 2)
+pub(crate) fn rsa_components(key: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+    let (len, rest) = match *key {
+        [0, high, low, ref rest @ ..] => (usize::from(u16::from_be_bytes([high, low])), rest),
+        [len, ref rest @ ..] => (usize::from(len), rest),
+        [] => return Err(Error::ParseError),
+    };
+    if len == 0 || rest.len() <= len {
+        return Err(Error::ParseError);
+    }
+    Ok(rest.split_at(len))
+}
+
+/// What validation made of an RRset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Signed, with a chain of trust from a trust anchor
+    Secure,
+    /// Provably outside any signed zone
+    Insecure,
+    /// Should be signed, but the signatures are missing, expired or do not verify
+    Bogus,
+    /// No chain of trust could be found either way, e.g. because a query failed or no
+    /// trust anchor covers the name
+    Indeterminate,
+}
+
+/// The keys whose zones are trusted without a DS record in the parent
+#[derive(Debug, Clone, Default)]
+pub struct TrustAnchors {
+    // Owner names are in canonical form.
+    ds: Vec<(Vec<u8>, DS)>,
+    keys: Vec<(Vec<u8>, DNSKEY)>,
+}
+
+impl TrustAnchors {
+    /// No trust anchors
+    pub fn new() -> TrustAnchors {
+        TrustAnchors::default()
+    }
+
+    /// The root zone's key-signing keys, KSK-2017 and KSK-2024
+    pub fn root() -> TrustAnchors {
+        let mut anchors = TrustAnchors::new();
+        for (key_tag, digest) in [
+            (
+                20326,
+                "E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D",
+            ),
+            (
+                38696,
+                "683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16",
+            ),
+        ] {
+            let ds = DS {
+                key_tag,
+                algorithm: RSASHA256,
+                digest_type: DIGEST_SHA256,
+                digest: decode_hex(digest).expect("root trust anchor"),
+            };
+            anchors.ds.push((vec![0], ds));
+        }
+        anchors
+    }
+
+    /// Read trust anchors in zone file format, as `parse()` does
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<TrustAnchors, Error> {
+        TrustAnchors::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parse DS and DNSKEY records in zone file format, such as
+    ///
+    /// ```text
+    /// . IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D
+    /// ```
+    ///
+    /// Owner names must be fully qualified.  The TTL and class are optional, records may
+    /// span lines within parentheses, and `;` starts a comment.  Directives such as
+    /// `$TTL` are ignored.
+    pub fn parse(text: &str) -> Result<TrustAnchors, Error> {
+        let mut anchors = TrustAnchors::new();
+        let mut tokens: Vec<&str> = Vec::new();
+        let mut depth = 0usize;
+        for line in text.lines() {
+            let line = match line.find(';') {
+                Some(i) => &line[..i],
+                None => line,
+            };
+            for c in line.chars() {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth = depth.checked_sub(1).ok_or(Error::InvalidTrustAnchor)?,
+                    _ => {}
+                }
+            }
+            tokens.extend(
+                line.split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+                    .filter(|t| !t.is_empty()),
+            );
+            if depth == 0 && !tokens.is_empty() {
+                if !tokens[0].starts_with('$') {
+                    anchors.add_record(&tokens)?;
+                }
+                tokens.clear();
+            }
+        }
+        if depth != 0 {
+            return Err(Error::InvalidTrustAnchor);
+        }
+        Ok(anchors)
+    }
+
+    fn add_record(&mut self, tokens: &[&str]) -> Result<(), Error> {
+        let (owner, mut rest) = tokens.split_first().ok_or(Error::InvalidTrustAnchor)?;
+        while let Some(token) = rest.first() {
+            if token.bytes().all(|b| b.is_ascii_digit()) || token.eq_ignore_ascii_case("IN") {
+                rest = &rest[1..];
+            } else {
+                break;
+            }
+        }
+        if rest.len() < 5 {
+            return Err(Error::InvalidTrustAnchor);
+        }
+        let data = rest[4..].concat();
+        if rest[0].eq_ignore_ascii_case("DS") {
+            let ds = DS {
+                key_tag: field(rest[1])?,
+                algorithm: field(rest[2])?,
+                digest_type: field(rest[3])?,
+                digest: decode_hex(&data).ok_or(Error::InvalidTrustAnchor)?,
+            };
+            self.add_ds(owner, ds)
+        } else if rest[0].eq_ignore_ascii_case("DNSKEY") {
+            let key = DNSKEY {
+                flags: field(rest[1])?,
+                protocol: field(rest[2])?,
+                algorithm: field(rest[3])?,
+                public_key: decode_base64(&data).ok_or(Error::InvalidTrustAnchor)?,
+            };
+            self.add_key(owner, key)
+        } else {
+            Err(Error::InvalidTrustAnchor)
+        }
+    }
+
+    /// Trust the key of `zone` that `ds` is the digest of
+    pub fn add_ds(&mut self, zone: &str, ds: DS) -> Result<(), Error> {
+        self.ds.push((wire::canonical_name(zone)?, ds));
+        Ok(())
+    }
+
+    /// Trust `key` as a key of `zone`
+    pub fn add_key(&mut self, zone: &str, key: DNSKEY) -> Result<(), Error> {
+        self.keys.push((wire::canonical_name(zone)?, key));
+        Ok(())
+    }
+
+    /// Whether there is an anchor for `zone`
+    pub fn covers(&self, zone: &str) -> bool {
+        wire::canonical_name(zone).is_ok_and(|zone| self.covers_canonical(&zone))
+    }
+
+    fn covers_canonical(&self, zone: &[u8]) -> bool {
+        self.ds.iter().any(|(z, _)| z == zone) || self.keys.iter().any(|(z, _)| z == zone)
+    }
+
+    // The DNSKEY anchors, of any zone
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &DNSKEY> {
+        self.keys.iter().map(|(_, key)| key)
+    }
+
+    // Whether `key` of `zone` is trusted
+    fn trusts(&self, zone: &[u8], name: &str, key: &DNSKEY) -> bool {
+        self.ds
+            .iter()
+            .any(|(z, ds)| z == zone && ds.matches(name, key))
+            || self.keys.iter().any(|(z, k)| z == zone && k == key)
+    }
+}
+
+fn field<T: FromStr>(token: &str) -> Result<T, Error> {
+    token.parse().map_err(|_| Error::InvalidTrustAnchor)
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+pub(crate) fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in text.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | u32::from(value);
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// The status of one RRset of a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RrsetStatus {
+    pub section: Section,
+    pub name: String,
+    pub rr_type: u16,
+    pub status: Status,
+}
+
+// What is known of a zone's keys
+#[derive(Debug, Clone)]
+enum KeyState {
+    /// The zone keys, validated
+    Secure(Vec<DNSKEY>),
+    Insecure,
+    Bogus,
+    Indeterminate,
+    /// Not a zone cut: the parent proves there is no delegation
+    NotZone,
+}
+
+/// Validates responses against trust anchors, remembering the keys of the zones it
+/// has validated
+#[derive(Debug, Clone)]
+pub struct Validator {
+    anchors: TrustAnchors,
+    // By canonical zone name
+    zones: HashMap<Vec<u8>, KeyState>,
+}
+
+impl Default for Validator {
+    fn default() -> Validator {
+        Validator::new(TrustAnchors::root())
+    }
+}
+
+impl Validator {
+    pub fn new(anchors: TrustAnchors) -> Validator {
+        Validator {
+            anchors,
+            zones: HashMap::new(),
+        }
+    }
+
+    /// Forget the zone keys learnt so far
+    pub fn clear(&mut self) {
+        self.zones.clear();
+    }
+
+    /// Query `dname` with DNSSEC records asked for, and validate the response.  NXDOMAIN
+    /// and NODATA answers are returned rather than errors, as their authority records may
+    /// be validated too.
+    pub fn query(
+        &mut self,
+        resolver: &mut Resolver,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+    ) -> Result<(Response, Vec<RrsetStatus>), Error> {
+        resolver.with_option(ResolverOption::UseDNSSEC, true, |resolver| {
+            let response = resolver.query_response(dname, class, typ)?;
+            let statuses = self.validate(resolver, &response)?;
+            Ok((response, statuses))
+        })
+    }
+
+    /// Validate each RRset in the answer and authority sections of `response`, fetching
+    /// keys with `resolver`.  DNSSEC records are asked for while doing so, as the
+    /// signatures are needed; `resolver`'s options are then left as they were.
+    pub fn validate(
+        &mut self,
+        resolver: &mut Resolver,
+        response: &Response,
+    ) -> Result<Vec<RrsetStatus>, Error> {
+        resolver.with_option(ResolverOption::UseDNSSEC, true, |resolver| {
+            self.validate_rrsets(resolver, response)
+        })
+    }
+
+    fn validate_rrsets(
+        &mut self,
+        resolver: &mut Resolver,
+        response: &Response,
+    ) -> Result<Vec<RrsetStatus>, Error> {
+        let mut statuses = Vec::new();
+        for section in [Section::Answer, Section::Authority] {
+            for rrset in rrsets(response, section)? {
+                statuses.push(RrsetStatus {
+                    section,
+                    name: rrset.name.clone(),
+                    rr_type: rrset.rr_type,
+                    status: self.rrset_status(resolver, response, &rrset),
+                });
+            }
+        }
+        Ok(statuses)
+    }
+
+    fn rrset_status(
+        &mut self,
+        resolver: &mut Resolver,
+        response: &Response,
+        rrset: &Rrset,
+    ) -> Status {
+        let mut signers: Vec<&str> = Vec::new();
+        for rrsig in &rrset.rrsigs {
+            let signer = match wire::labels(rrsig.signer_name.as_bytes()) {
+                Ok(signer) => signer,
+                Err(_) => continue,
+            };
+            // DS records are signed by the parent zone, everything else by its own.
+            let in_zone = wire::is_subdomain(&rrset.labels, &signer)
+                && (rrset.rr_type != RecordType::DS as u16 || rrset.labels.len() > signer.len());
+            if in_zone && !signers.contains(&&rrsig.signer_name[..]) {
+                signers.push(&rrsig.signer_name);
+            }
+        }
+        if signers.is_empty() {
+            return self.unsigned_status(resolver, rrset);
+        }
+
+        let (mut insecure, mut bogus) = (false, false);
+        for signer in signers {
+            match self.zone_keys(resolver, signer) {
+                KeyState::Secure(keys) => match rrset.signature(signer, &keys) {
+                    Some(rrsig) if expansion_proved(response, rrset, rrsig, signer, &keys) => {
+                        return Status::Secure;
+                    }
+                    _ => bogus = true,
+                },
+                KeyState::Insecure => insecure = true,
+                KeyState::Bogus | KeyState::NotZone => bogus = true,
+                KeyState::Indeterminate => {}
+            }
+        }
+        if insecure {
+            Status::Insecure
+        } else if bogus {
+            Status::Bogus
+        } else {
+            Status::Indeterminate
+        }
+    }
+
+    // An RRset without signatures is bogus in a secure zone
+    fn unsigned_status(&mut self, resolver: &mut Resolver, rrset: &Rrset) -> Status {
+        let mut labels = &rrset.labels[..];
+        if rrset.rr_type == RecordType::DS as u16 && !labels.is_empty() {
+            labels = &labels[1..];
+        }
+        match self.enclosing_zone(resolver, labels).1 {
+            KeyState::Secure(_) | KeyState::Bogus | KeyState::NotZone => Status::Bogus,
+            KeyState::Insecure => Status::Insecure,
+            KeyState::Indeterminate => Status::Indeterminate,
+        }
+    }
+
+    // The zone `labels` is in, and its state, found by walking down from the closest
+    // trust anchor.  Each name on the way is checked with its parent for a zone cut, so
+    // only signed DS records and denial records place the cuts.
+    fn enclosing_zone(
+        &mut self,
+        resolver: &mut Resolver,
+        labels: &[Vec<u8>],
+    ) -> (String, KeyState) {
+        let anchored = (0..=labels.len()).find(|&i| {
+            wire::canonical_name(&wire::name_to_string(&labels[i..]))
+                .is_ok_and(|zone| self.anchors.covers_canonical(&zone))
+        });
+        let mut cut = match anchored {
+            Some(cut) => cut,
+            None => return (wire::name_to_string(labels), KeyState::Indeterminate),
+        };
+        let mut state = self.zone_keys(resolver, &wire::name_to_string(&labels[cut..]));
+        for i in (0..cut).rev() {
+            if !matches!(state, KeyState::Secure(_)) {
+                break;
+            }
+            match self.zone_keys(resolver, &wire::name_to_string(&labels[i..])) {
+                KeyState::NotZone => {}
+                child => {
+                    cut = i;
+                    state = child;
+                }
+            }
+        }
+        (wire::name_to_string(&labels[cut..]), state)
+    }
+
+    // The validated keys of `zone`, from the cache if possible
+    fn zone_keys(&mut self, resolver: &mut Resolver, zone: &str) -> KeyState {
+        let canonical = match wire::canonical_name(zone) {
+            Ok(canonical) => canonical,
+            Err(_) => return KeyState::Bogus,
+        };
+        if let Some(state) = self.zones.get(&canonical) {
+            return state.clone();
+        }
+        // A zone depending on itself is indeterminate.
+        self.zones
+            .insert(canonical.clone(), KeyState::Indeterminate);
+        let state = self.find_zone_keys(resolver, zone, &canonical);
+        self.zones.insert(canonical, state.clone());
+        state
+    }
+
+    fn find_zone_keys(
+        &mut self,
+        resolver: &mut Resolver,
+        zone: &str,
+        canonical: &[u8],
+    ) -> KeyState {
+        if self.anchors.covers_canonical(canonical) {
+            let anchors = &self.anchors;
+            return fetch_keys(resolver, zone, |key| anchors.trusts(canonical, zone, key));
+        }
+        let labels = match wire::labels(zone.as_bytes()) {
+            Ok(labels) if !labels.is_empty() => labels,
+            Ok(_) => return KeyState::Indeterminate,
+            Err(_) => return KeyState::Bogus,
+        };
+        let (parent, keys) = match self.enclosing_zone(resolver, &labels[1..]) {
+            (parent, KeyState::Secure(keys)) => (parent, keys),
+            (_, state) => return state,
+        };
+
+        let response = match send(resolver, zone, RecordType::DS) {
+            Some(response) => response,
+            None => return KeyState::Indeterminate,
+        };
+        let answer = match rrsets(&response, Section::Answer) {
+            Ok(answer) => answer,
+            Err(_) => return KeyState::Bogus,
+        };
+        let ds_rrset = answer
+            .iter()
+            .find(|r| r.rr_type == RecordType::DS as u16 && r.is_owned_by(canonical));
+        let ds_rrset = match ds_rrset {
+            Some(ds_rrset) => ds_rrset,
+            None => return denial_status(&parent, &keys, zone, &response),
+        };
+        if !ds_rrset.verify(&parent, &keys) {
+            return KeyState::Bogus;
+        }
+        let ds: Vec<DS> = ds_rrset
+            .rrs
+            .iter()
+            .filter_map(|rr| DS::extract(rr).ok())
+            .filter(|ds| is_supported(ds.algorithm) && digest(ds.digest_type, &[]).is_ok())
+            .collect();
+        if ds.is_empty() {
+            return KeyState::Insecure;
+        }
+        fetch_keys(resolver, zone, |key| {
+            ds.iter().any(|ds| ds.matches(zone, key))
+        })
+    }
+}
+
+// The state of `zone`, for which `parent` (with `keys`) returned no DS records: insecure
+// if the parent's signed NSEC or NSEC3 records prove it an unsigned delegation, and no
+// zone if they prove it no delegation at all.  Denial records of other zones, signed or
+// not, are not considered.
+fn denial_status(parent: &str, keys: &[DNSKEY], zone: &str, response: &Response) -> KeyState {
+    let (authority, parent_labels) = match (
+        rrsets(response, Section::Authority),
+        wire::labels(parent.as_bytes()),
+    ) {
+        (Ok(authority), Ok(parent_labels)) => (authority, parent_labels),
+        _ => return KeyState::Bogus,
+    };
+    let proof: Vec<&Rrset> = authority
+        .iter()
+        .filter(|r| {
+            [RecordType::NSEC, RecordType::NSEC3, RecordType::SOA]
+                .iter()
+                .any(|&t| r.rr_type == t as u16)
+                && wire::is_subdomain(&r.labels, &parent_labels)
+                && r.is_signed_as(&parent_labels)
+        })
+        .collect();
+    if proof.is_empty() || !proof.iter().all(|r| r.verify(parent, keys)) {
+        return KeyState::Bogus;
+    }
+    let nsec: Vec<Record<NSEC>> = records(&proof);
+    let nsec3: Vec<Record<NSEC3>> = records(&proof);
+    let prove = |rr_type: RecordType| denial::prove_records(&nsec, &nsec3, zone, rr_type as u16);
+    match prove(RecordType::DS) {
+        Ok(Denial::NoData) | Ok(Denial::OptOut) => return KeyState::Insecure,
+        Ok(Denial::NxDomain) => return KeyState::NotZone,
+        _ => {}
+    }
+    match prove(RecordType::NS) {
+        Ok(Denial::NoData) => KeyState::NotZone,
+        _ if nsec3
+            .iter()
+            .any(|r| r.data.iterations > MAX_NSEC3_ITERATIONS) =>
+        {
+            KeyState::Insecure
+        }
+        _ => KeyState::Bogus,
+    }
+}
+
+// Whether `rrset`, signed with `rrsig` by `zone`, is not a wildcard expansion, or the
+// zone's signed NSEC or NSEC3 records in `response` prove that no closer match for its
+// name exists (RFC 4035 section 5.3.4)
+fn expansion_proved(
+    response: &Response,
+    rrset: &Rrset,
+    rrsig: &RRSIG,
+    zone: &str,
+    keys: &[DNSKEY],
+) -> bool {
+    let wildcard = rrset.labels.first().is_some_and(|label| label == b"*");
+    if usize::from(rrsig.labels) + usize::from(wildcard) >= rrset.labels.len() {
+        return true;
+    }
+    let authority = match rrsets(response, Section::Authority) {
+        Ok(authority) => authority,
+        Err(_) => return false,
+    };
+    let proof: Vec<&Rrset> = authority
+        .iter()
+        .filter(|r| {
+            (r.rr_type == RecordType::NSEC as u16 || r.rr_type == RecordType::NSEC3 as u16)
+                && r.verify(zone, keys)
+        })
+        .collect();
+    denial::proves_expansion(
+        &records(&proof),
+        &records(&proof),
+        &rrset.name,
+        rrsig.labels,
+    )
+    .unwrap_or(false)
+}
+
+// The records of type `T` in `rrsets`
+fn records<T: RecordData>(rrsets: &[&Rrset]) -> Vec<Record<T>> {
+    rrsets
+        .iter()
+        .filter(|r| r.rr_type == T::get_record_type() as u16)
+        .flat_map(|r| r.rrs.iter().filter_map(|rr| Record::extract(rr).ok()))
+        .collect()
+}
+
+// Fetch the DNSKEY RRset of `zone`, and trust its zone keys if it is signed by one of
+// them that `trusted` accepts
+fn fetch_keys<F: Fn(&DNSKEY) -> bool>(resolver: &mut Resolver, zone: &str, trusted: F) -> KeyState {
+    let response = match send(resolver, zone, RecordType::DNSKEY) {
+        Some(response) => response,
+        None => return KeyState::Indeterminate,
+    };
+    let canonical = match wire::canonical_name(zone) {
+        Ok(canonical) => canonical,
+        Err(_) => return KeyState::Bogus,
+    };
+    let answer = match rrsets(&response, Section::Answer) {
+        Ok(answer) => answer,
+        Err(_) => return KeyState::Bogus,
+    };
+    let rrset = match answer
+        .iter()
+        .find(|r| r.rr_type == RecordType::DNSKEY as u16 && r.is_owned_by(&canonical))
+    {
+        Some(rrset) => rrset,
+        None => return KeyState::Bogus,
+    };
+    let keys: Vec<DNSKEY> = rrset
+        .rrs
+        .iter()
+        .filter_map(|rr| DNSKEY::extract(rr).ok())
+        .filter(|key| key.is_zone_key() && !key.is_revoked())
+        .collect();
+    let entry: Vec<DNSKEY> = keys.iter().filter(|key| trusted(key)).cloned().collect();
+    if rrset.verify(zone, &entry) {
+        KeyState::Secure(keys)
+    } else {
+        KeyState::Bogus
+    }
+}
+
+fn send(resolver: &mut Resolver, name: &str, typ: RecordType) -> Option<Response> {
+    // Owner names give the root as "".
+    let name = if name.is_empty() { "." } else { name };
+    resolver
+        .query_response(name.as_bytes(), Class::IN, typ)
+        .ok()
+}
+
+// Records of a response with the same owner, class and type, and their signatures
+pub(crate) struct Rrset<'a> {
+    pub(crate) name: String,
+    pub(crate) labels: Vec<Vec<u8>>,
+    pub(crate) rr_type: u16,
+    pub(crate) rr_class: u16,
+    pub(crate) rrs: Vec<Rr<'a>>,
+    pub(crate) rrsigs: Vec<RRSIG>,
+}
+
+impl Rrset<'_> {
+    fn is_owned_by(&self, canonical: &[u8]) -> bool {
+        wire::canonical_name(&self.name).is_ok_and(|name| name == canonical)
+    }
+
+    // Whether the RRset has a signature by the zone `zone`, valid or not
+    fn is_signed_as(&self, zone: &[Vec<u8>]) -> bool {
+        self.rrsigs.iter().any(|rrsig| {
+            wire::labels(rrsig.signer_name.as_bytes()).is_ok_and(|s| wire::names_equal(&s, zone))
+        })
+    }
+
+    // Whether one of `keys` made a current signature of this RRset as `zone`
+    fn verify(&self, zone: &str, keys: &[DNSKEY]) -> bool {
+        self.signature(zone, keys).is_some()
+    }
+
+    // A current signature of this RRset by one of `keys` as `zone`
+    fn signature(&self, zone: &str, keys: &[DNSKEY]) -> Option<&RRSIG> {
+        let zone = wire::labels(zone.as_bytes()).unwrap_or_default();
+        self.rrsigs.iter().find(|rrsig| {
+            let signer = wire::labels(rrsig.signer_name.as_bytes()).unwrap_or_default();
+            wire::names_equal(&signer, &zone)
+                && rrsig.is_current()
+                && keys.iter().any(|key| self.is_signed_by(rrsig, key))
+        })
+    }
+
+    // Whether `rrsig` is `key`'s signature of this RRset, whether or not it is current
+    pub(crate) fn is_signed_by(&self, rrsig: &RRSIG, key: &DNSKEY) -> bool {
+        if key.key_tag() != rrsig.key_tag
+            || key.algorithm != rrsig.algorithm
+            || usize::from(rrsig.labels) > self.labels.len()
+        {
+            return false;
+        }
+        let records = self
+            .rrs
+            .iter()
+            .map(|rr| Ok((rr.name.clone(), rr.rr_class, record::canonical_rdata(rr)?)))
+            .collect::<Result<Vec<_>, Error>>();
+        records
+            .and_then(|records| rrsig.signed_data_raw(records))
+            .is_ok_and(|data| verify(key, rrsig, &data).is_ok())
+    }
+}
+
+pub(crate) fn rrsets(response: &Response, section: Section) -> Result<Vec<Rrset<'_>>, Error> {
+    let mut rrsets: Vec<Rrset> = Vec::new();
+    let mut rrsigs = Vec::new();
+    for i in 0..response.get_section_count(section) {
+        let rr = response.get_rr(section, i)?;
+        if rr.type_ == RecordType::OPT as u16 {
+            continue;
+        }
+        let labels = wire::labels(rr.name.as_bytes())?;
+        if rr.type_ == RecordType::RRSIG as u16 {
+            rrsigs.push((labels, rr.rr_class, RRSIG::extract(&rr)?));
+            continue;
+        }
+        match rrsets.iter_mut().find(|r| {
+            r.rr_type == rr.type_
+                && r.rr_class == rr.rr_class
+                && wire::names_equal(&r.labels, &labels)
+        }) {
+            Some(rrset) => rrset.rrs.push(rr),
+            None => rrsets.push(Rrset {
+                name: rr.name.clone(),
+                labels,
+                rr_type: rr.type_,
+                rr_class: rr.rr_class,
+                rrs: vec![rr],
+                rrsigs: Vec::new(),
+            }),
+        }
+    }
+    for (labels, class, rrsig) in rrsigs {
+        if let Some(rrset) = rrsets.iter_mut().find(|r| {
+            r.rr_type == rrsig.type_covered
+                && r.rr_class == class
+                && wire::names_equal(&r.labels, &labels)
+        }) {
+            rrset.rrsigs.push(rrsig);
+        }
+    }
+    Ok(rrsets)
+}
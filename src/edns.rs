@@ -0,0 +1,484 @@
+//! EDNS(0) (RFC 6891): the OPT pseudo-record and its options.
+//!
+//! A response's OPT record is decoded by `Response::edns()`.  Options to send with
+//! queries are set with `Resolver::set_edns_options()` or `Transport::set_edns_options()`;
+//! setting any implies EDNS(0), as if `ResolverOption::UseEDNS0` were set.
+
+use crate::error::Error;
+use crate::record::RecordType;
+use crate::response::{Response, Section};
+use crate::transport::EDNS_UDP_SIZE;
+use crate::wire;
+use byteorder::{BigEndian, ByteOrder};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Option codes
+pub const OPTION_NSID: u16 = 3;
+pub const OPTION_CLIENT_SUBNET: u16 = 8;
+pub const OPTION_COOKIE: u16 = 10;
+pub const OPTION_KEEPALIVE: u16 = 11;
+pub const OPTION_PADDING: u16 = 12;
+pub const OPTION_EXTENDED_ERROR: u16 = 15;
+
+// The DO bit, in the flags held in the low half of the OPT record's TTL
+const FLAG_DO: u32 = 0x8000;
+
+/// The contents of an OPT record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edns {
+    /// Largest UDP payload the sender can receive
+    pub udp_size: u16,
+    /// Upper eight bits of the response code
+    pub extended_rcode: u8,
+    pub version: u8,
+    /// Whether DNSSEC records are wanted
+    pub dnssec_ok: bool,
+    pub options: Vec<EdnsOption>,
+}
+
+/// A client subnet (RFC 7871): the network a query is made on behalf of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientSubnet {
+    /// The network address; bits beyond `source_prefix` are zero
+    pub address: IpAddr,
+    /// Number of leading bits of the address given
+    pub source_prefix: u8,
+    /// Number of leading bits the answer depends on (zero in queries)
+    pub scope_prefix: u8,
+}
+
+impl ClientSubnet {
+    /// The network of `source_prefix` bits containing `address`, as sent in a query.  The
+    /// prefix is limited to the length of the address.
+    pub fn new(address: IpAddr, source_prefix: u8) -> ClientSubnet {
+        let source_prefix = source_prefix.min(address_bits(address));
+        ClientSubnet {
+            address: mask(address, source_prefix),
+            source_prefix,
+            scope_prefix: 0,
+        }
+    }
+}
+
+fn address_bits(address: IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// `address` with all but its first `prefix` bits cleared
+pub(crate) fn mask(address: IpAddr, prefix: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(a) => {
+            let bits = u32::MAX
+                .checked_shl(32 - u32::from(prefix.min(32)))
+                .unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(a) & bits))
+        }
+        IpAddr::V6(a) => {
+            let bits = u128::MAX
+                .checked_shl(128 - u32::from(prefix.min(128)))
+                .unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(a) & bits))
+        }
+    }
+}
+
+/// A DNS cookie (RFC 7873)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub client: [u8; 8],
+    /// The server cookie, 8 to 32 bytes, or empty if there is none yet
+    pub server: Vec<u8>,
+}
+
+/// An extended DNS error (RFC 8914)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedError {
+    pub info_code: u16,
+    pub extra_text: String,
+}
+
+impl ExtendedError {
+    /// The registered meaning of the info code, if it is known
+    pub fn purpose(&self) -> Option<&'static str> {
+        let purpose = match self.info_code {
+            0 => "Other Error",
+            1 => "Unsupported DNSKEY Algorithm",
+            2 => "Unsupported DS Digest Type",
+            3 => "Stale Answer",
+            4 => "Forged Answer",
+            5 => "DNSSEC Indeterminate",
+            6 => "DNSSEC Bogus",
+            7 => "Signature Expired",
+            8 => "Signature Not Yet Valid",
+            9 => "DNSKEY Missing",
+            10 => "RRSIGs Missing",
+            11 => "No Zone Key Bit Set",
+            12 => "NSEC Missing",
+            13 => "Cached Error",
+            14 => "Not Ready",
+            15 => "Blocked",
+            16 => "Censored",
+            17 => "Filtered",
+            18 => "Prohibited",
+            19 => "Stale NXDomain Answer",
+            20 => "Not Authoritative",
+            21 => "Not Supported",
+            22 => "No Reachable Authority",
+            23 => "Network Error",
+            24 => "Invalid Data",
+            25 => "Signature Expired before Valid",
+            26 => "Too Early",
+            27 => "Unsupported NSEC3 Iterations Value",
+            28 => "Unable to conform to policy",
+            29 => "Synthesized",
+            30 => "Invalid Query Type",
+            _ => return None,
+        };
+        Some(purpose)
+    }
+}
+
+/// As dig shows it, e.g. `EDE: 15 (Blocked): (ad server)`
+impl fmt::Display for ExtendedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EDE: {}", self.info_code)?;
+        if let Some(purpose) = self.purpose() {
+            write!(f, " ({})", purpose)?;
+        }
+        if !self.extra_text.is_empty() {
+            write!(f, ": ({})", self.extra_text)?;
+        }
+        Ok(())
+    }
+}
+
+/// An option in an OPT record.  Options that are unknown, or do not decode, are kept as
+/// `Unknown`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdnsOption {
+    /// Name server identifier (RFC 5001).  Empty in queries, to ask for it.
+    Nsid(Vec<u8>),
+    ClientSubnet(ClientSubnet),
+    Cookie(Cookie),
+    /// TCP keepalive (RFC 7828), in units of 100 milliseconds.  Empty in queries.
+    Keepalive(Option<u16>),
+    /// Padding (RFC 7830) of this many zero bytes
+    Padding(u16),
+    ExtendedError(ExtendedError),
+    Unknown(u16, Vec<u8>),
+}
+
+impl EdnsOption {
+    pub fn code(&self) -> u16 {
+        match *self {
+            EdnsOption::Nsid(_) => OPTION_NSID,
+            EdnsOption::ClientSubnet(_) => OPTION_CLIENT_SUBNET,
+            EdnsOption::Cookie(_) => OPTION_COOKIE,
+            EdnsOption::Keepalive(_) => OPTION_KEEPALIVE,
+            EdnsOption::Padding(_) => OPTION_PADDING,
+            EdnsOption::ExtendedError(_) => OPTION_EXTENDED_ERROR,
+            EdnsOption::Unknown(code, _) => code,
+        }
+    }
+
+    /// Decode an option from its code and data
+    pub fn parse(code: u16, data: &[u8]) -> EdnsOption {
+        let parsed = match code {
+            OPTION_NSID => Some(EdnsOption::Nsid(data.to_vec())),
+            OPTION_CLIENT_SUBNET => parse_client_subnet(data).map(EdnsOption::ClientSubnet),
+            OPTION_COOKIE => parse_cookie(data).map(EdnsOption::Cookie),
+            OPTION_KEEPALIVE => match data.len() {
+                0 => Some(EdnsOption::Keepalive(None)),
+                2 => Some(EdnsOption::Keepalive(Some(BigEndian::read_u16(data)))),
+                _ => None,
+            },
+            OPTION_PADDING => Some(EdnsOption::Padding(data.len() as u16)),
+            OPTION_EXTENDED_ERROR if data.len() >= 2 => {
+                Some(EdnsOption::ExtendedError(ExtendedError {
+                    info_code: BigEndian::read_u16(data),
+                    extra_text: String::from_utf8_lossy(&data[2..])
+                        .trim_end_matches('\0')
+                        .to_owned(),
+                }))
+            }
+            _ => None,
+        };
+        parsed.unwrap_or_else(|| EdnsOption::Unknown(code, data.to_vec()))
+    }
+
+    /// The option's data, as sent
+    pub fn data(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match *self {
+            EdnsOption::Nsid(ref data) | EdnsOption::Unknown(_, ref data) => {
+                out.extend_from_slice(data)
+            }
+            EdnsOption::ClientSubnet(ref subnet) => {
+                let (family, octets) = match subnet.address {
+                    IpAddr::V4(a) => (1, a.octets().to_vec()),
+                    IpAddr::V6(a) => (2, a.octets().to_vec()),
+                };
+                wire::push_u16(&mut out, family);
+                out.push(subnet.source_prefix);
+                out.push(subnet.scope_prefix);
+                let len = (subnet.source_prefix as usize)
+                    .div_ceil(8)
+                    .min(octets.len());
+                out.extend_from_slice(&octets[..len]);
+            }
+            EdnsOption::Cookie(ref cookie) => {
+                out.extend_from_slice(&cookie.client);
+                out.extend_from_slice(&cookie.server);
+            }
+            EdnsOption::Keepalive(timeout) => {
+                if let Some(timeout) = timeout {
+                    wire::push_u16(&mut out, timeout);
+                }
+            }
+            EdnsOption::Padding(len) => out.resize(len as usize, 0),
+            EdnsOption::ExtendedError(ref error) => {
+                wire::push_u16(&mut out, error.info_code);
+                out.extend_from_slice(error.extra_text.as_bytes());
+            }
+        }
+        out
+    }
+}
+
+fn parse_client_subnet(data: &[u8]) -> Option<ClientSubnet> {
+    if data.len() < 4 {
+        return None;
+    }
+    let family = BigEndian::read_u16(data);
+    let (source_prefix, scope_prefix) = (data[2], data[3]);
+    let address = &data[4..];
+    let address = match family {
+        1 if source_prefix <= 32 && address.len() <= 4 => {
+            let mut octets = [0; 4];
+            octets[..address.len()].copy_from_slice(address);
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        2 if source_prefix <= 128 && address.len() <= 16 => {
+            let mut octets = [0; 16];
+            octets[..address.len()].copy_from_slice(address);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+    Some(ClientSubnet {
+        address,
+        source_prefix,
+        scope_prefix,
+    })
+}
+
+fn parse_cookie(data: &[u8]) -> Option<Cookie> {
+    if data.len() != 8 && !(16..=40).contains(&data.len()) {
+        return None;
+    }
+    let mut client = [0; 8];
+    client.copy_from_slice(&data[..8]);
+    Some(Cookie {
+        client,
+        server: data[8..].to_vec(),
+    })
+}
+
+impl Default for Edns {
+    fn default() -> Edns {
+        Edns::new()
+    }
+}
+
+impl Edns {
+    /// EDNS version 0, advertising `EDNS_UDP_SIZE`, with no options
+    pub fn new() -> Edns {
+        Edns {
+            udp_size: EDNS_UDP_SIZE,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: Vec::new(),
+        }
+    }
+
+    /// Decode an OPT record from its class (the UDP size), TTL and data
+    pub fn parse(class: u16, ttl: u32, data: &[u8]) -> Result<Edns, Error> {
+        let mut options = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let header = data.get(pos..pos + 4).ok_or(Error::ParseError)?;
+            let code = BigEndian::read_u16(&header[0..2]);
+            let len = BigEndian::read_u16(&header[2..4]) as usize;
+            let value = data.get(pos + 4..pos + 4 + len).ok_or(Error::ParseError)?;
+            options.push(EdnsOption::parse(code, value));
+            pos += 4 + len;
+        }
+        Ok(Edns {
+            udp_size: class,
+            extended_rcode: (ttl >> 24) as u8,
+            version: (ttl >> 16) as u8,
+            dnssec_ok: ttl & FLAG_DO != 0,
+            options,
+        })
+    }
+
+    /// The OPT record to send with a query, if any: when options are to be sent, or
+    /// EDNS(0) or DNSSEC records are asked for
+    pub(crate) fn for_query(options: &[EdnsOption], edns0: bool, dnssec: bool) -> Option<Edns> {
+        if options.is_empty() && !edns0 && !dnssec {
+            return None;
+        }
+        Some(Edns {
+            dnssec_ok: dnssec,
+            options: options.to_vec(),
+            ..Edns::new()
+        })
+    }
+
+    /// The name server identifier, if the server sent one
+    pub fn nsid(&self) -> Option<&[u8]> {
+        self.options.iter().find_map(|o| match *o {
+            EdnsOption::Nsid(ref nsid) => Some(&nsid[..]),
+            _ => None,
+        })
+    }
+
+    /// The client subnet the answer is for, if the server sent one
+    pub fn client_subnet(&self) -> Option<&ClientSubnet> {
+        self.options.iter().find_map(|o| match *o {
+            EdnsOption::ClientSubnet(ref subnet) => Some(subnet),
+            _ => None,
+        })
+    }
+
+    /// Append this as an OPT record to `msg`, counting it in the header
+    pub(crate) fn write(&self, msg: &mut Vec<u8>) {
+        let arcount = BigEndian::read_u16(&msg[10..12]);
+        BigEndian::write_u16(&mut msg[10..12], arcount + 1);
+
+        msg.push(0); // root owner name
+        wire::push_u16(msg, RecordType::OPT as u16);
+        wire::push_u16(msg, self.udp_size);
+        let mut ttl = (u32::from(self.extended_rcode) << 24) | (u32::from(self.version) << 16);
+        if self.dnssec_ok {
+            ttl |= FLAG_DO;
+        }
+        wire::push_u32(msg, ttl);
+
+        let mut data = Vec::new();
+        for option in &self.options {
+            let value = option.data();
+            wire::push_u16(&mut data, option.code());
+            wire::push_u16(&mut data, value.len() as u16);
+            data.extend_from_slice(&value);
+        }
+        wire::push_u16(msg, data.len() as u16);
+        msg.extend_from_slice(&data);
+    }
+}
+
+impl Response {
+    /// The OPT record in the additional section, if there is one
+    pub fn edns(&self) -> Result<Option<Edns>, Error> {
+        for i in 0..self.get_section_count(Section::Additional) {
+            let rr = self.get_rr(Section::Additional, i)?;
+            if rr.type_ == RecordType::OPT as u16 {
+                return Edns::parse(rr.rr_class, rr.ttl, rr.rdata()).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The client subnet the answer is for, if the server sent one.  Its `scope_prefix`
+    /// gives how much of the address the answer depends on; zero means it applies
+    /// everywhere.
+    pub fn client_subnet(&self) -> Result<Option<ClientSubnet>, Error> {
+        Ok(self.edns()?.and_then(|edns| edns.client_subnet().cloned()))
+    }
+
+    /// The extended DNS errors (RFC 8914) the server gave, e.g. with a stale answer.  For
+    /// a failed query, these are part of the error instead.
+    pub fn extended_errors(&self) -> Result<Vec<ExtendedError>, Error> {
+        let options = match self.edns()? {
+            Some(edns) => edns.options,
+            None => return Ok(Vec::new()),
+        };
+        Ok(options
+            .into_iter()
+            .filter_map(|o| match o {
+                EdnsOption::ExtendedError(e) => Some(e),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+/// A copy of the message `msg` with `option` added to its OPT record, which is added if
+/// there is none
+pub(crate) fn add_option(msg: &[u8], option: &EdnsOption) -> Result<Vec<u8>, Error> {
+    let header = wire::Header::parse(msg)?;
+    let mut pos = wire::HEADER_LEN;
+    for _ in 0..header.qdcount {
+        pos = wire::read_name(msg, pos)?.1 + 4;
+    }
+    let records = u32::from(header.ancount) + u32::from(header.nscount) + u32::from(header.arcount);
+    for _ in 0..records {
+        pos = wire::read_name(msg, pos)?.1;
+        let fixed = msg.get(pos..pos + 10).ok_or(Error::ParseError)?;
+        let typ = BigEndian::read_u16(&fixed[0..2]);
+        let rdlength = BigEndian::read_u16(&fixed[8..10]) as usize;
+        let end = pos + 10 + rdlength;
+        if end > msg.len() {
+            return Err(Error::ParseError);
+        }
+        if typ == RecordType::OPT as u16 {
+            let value = option.data();
+            let mut added = Vec::with_capacity(4 + value.len());
+            wire::push_u16(&mut added, option.code());
+            wire::push_u16(&mut added, value.len() as u16);
+            added.extend_from_slice(&value);
+
+            let mut out = msg[..end].to_vec();
+            BigEndian::write_u16(&mut out[pos + 8..pos + 10], (rdlength + added.len()) as u16);
+            out.extend_from_slice(&added);
+            out.extend_from_slice(&msg[end..]);
+            return Ok(out);
+        }
+        pos = end;
+    }
+    let mut out = msg.to_vec();
+    Edns {
+        options: vec![option.clone()],
+        ..Edns::new()
+    }
+    .write(&mut out);
+    Ok(out)
+}
+
+/// `wire::check_rcode()`, with any extended errors in the answer made part of the error
+pub(crate) fn check_rcode(answer: &[u8]) -> Result<(), Error> {
+    wire::check_rcode(answer).map_err(|e| with_extended_errors(e, answer))
+}
+
+/// Make the extended errors in `answer`, if any, part of `error`
+pub(crate) fn with_extended_errors(error: Error, answer: &[u8]) -> Error {
+    let error = match error {
+        Error::Resolver(error) => error,
+        e => return e,
+    };
+    let response = Response::from_buffer(answer.to_vec());
+    let extended = match response.as_ref().map(|r| r.extended_errors()) {
+        Ok(Ok(extended)) if !extended.is_empty() => extended,
+        _ => return Error::Resolver(error),
+    };
+    Error::Extended {
+        error,
+        rcode: response.unwrap().rcode(),
+        extended,
+    }
+}
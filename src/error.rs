@@ -1,7 +1,9 @@
-use crate::Section;
+use crate::edns::ExtendedError;
+use crate::{Rcode, Section};
 use std::convert::From;
 use std::ffi::{FromBytesWithNulError, NulError};
 use std::fmt;
+use std::io;
 use std::str::Utf8Error;
 
 #[derive(Clone, PartialEq, Eq)]
@@ -35,6 +37,12 @@ impl fmt::Debug for ResolutionError {
 pub enum Error {
     /// Name Resolution failed
     Resolver(ResolutionError),
+    /// Name resolution failed, and the server said why with extended DNS errors (RFC 8914)
+    Extended {
+        error: ResolutionError,
+        rcode: Rcode,
+        extended: Vec<ExtendedError>,
+    },
     /// String contains null bytes
     CString(NulError),
     /// Stirng contains null bytes
@@ -53,11 +61,58 @@ pub enum Error {
     Utf8(Utf8Error),
     /// Unknown class
     UnknownClass(u16),
+    /// I/O error, e.g. reading a configuration file
+    Io(io::ErrorKind),
+    /// Resolver could not be initialised
+    Init,
+    /// Query did not complete in time
+    Timeout,
+    /// Query was cancelled before it completed
+    Cancelled,
+    /// Domain name is not valid
+    InvalidName,
+    /// CNAME or DNAME records form a loop
+    AliasLoop,
+    /// Too many CNAME or DNAME records were followed
+    AliasChainTooLong,
+    /// Response ID does not match the query
+    IdMismatch,
+    /// Response question does not match the query
+    QuestionMismatch,
+    /// DNSSEC algorithm or digest type is not supported
+    UnsupportedAlgorithm(u8),
+    /// DNSSEC signature does not verify
+    BadSignature,
+    /// Trust anchor file does not parse
+    InvalidTrustAnchor,
+    /// Signing key does not parse, or does not match its DNSKEY record
+    InvalidKey,
+    /// Zone cannot be signed, e.g. for want of an SOA record at the apex
+    InvalidZone,
+    /// Zone has no ZONEMD record for its serial, or the digest does not match
+    BadZoneDigest,
+    /// NSEC3 records have more iterations than are hashed
+    TooManyIterations,
+    /// NAPTR substitution expression does not parse
+    InvalidRegexp,
+    /// Telephone number is not an E.164 number, e.g. for want of a leading "+"
+    InvalidNumber,
 }
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::Resolver(ref e) => write!(f, "{}: {:?}", self.description(), e),
+            Error::Extended {
+                ref error,
+                rcode,
+                ref extended,
+            } => {
+                write!(f, "{}: {:?} ({}", self.description(), error, rcode)?;
+                for e in extended {
+                    write!(f, ", {}", e)?;
+                }
+                write!(f, ")")
+            }
             Error::CString(ref e) => write!(
                 f,
                 "Name supplied contains a null byte at \
@@ -73,14 +128,32 @@ impl fmt::Debug for Error {
             ),
             Error::Utf8(ref e) => write!(f, "{}: {:?}", self.description(), e),
             Error::UnknownClass(u) => write!(f, "{}: {}", self.description(), u),
+            Error::UnsupportedAlgorithm(a) => write!(f, "{}: {}", self.description(), a),
+            Error::Io(k) => write!(f, "{}: {:?}", self.description(), k),
             _ => write!(f, "{}", self.description()),
         }
     }
 }
 impl Error {
+    /// The reason name resolution failed, whether or not the server gave extended errors
+    pub fn resolution_error(&self) -> Option<&ResolutionError> {
+        match *self {
+            Error::Resolver(ref error) | Error::Extended { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// The extended errors the server gave for a failure, if any
+    pub fn extended_errors(&self) -> &[ExtendedError] {
+        match *self {
+            Error::Extended { ref extended, .. } => extended,
+            _ => &[],
+        }
+    }
+
     fn description(&self) -> &str {
         match *self {
-            Error::Resolver(_) => "Name Resolution failed",
+            Error::Resolver(_) | Error::Extended { .. } => "Name Resolution failed",
             Error::CString(_) => "Name supplied contains a null byte",
             Error::CStr(_) => "CStr failed",
             Error::ParseError => "Name service response does not parse",
@@ -90,11 +163,29 @@ impl Error {
             Error::WrongRRType => "Wrong Resource Record type",
             Error::Utf8(_) => "UTF-8 error",
             Error::UnknownClass(_) => "Unknown class",
+            Error::Io(_) => "I/O error",
+            Error::Init => "Resolver initialisation failed",
+            Error::Timeout => "Query timed out",
+            Error::Cancelled => "Query was cancelled",
+            Error::InvalidName => "Domain name is not valid",
+            Error::AliasLoop => "Alias chain loops",
+            Error::AliasChainTooLong => "Alias chain is too long",
+            Error::IdMismatch => "Response ID does not match the query",
+            Error::QuestionMismatch => "Response question does not match the query",
+            Error::UnsupportedAlgorithm(_) => "Unsupported DNSSEC algorithm or digest type",
+            Error::BadSignature => "DNSSEC signature does not verify",
+            Error::InvalidTrustAnchor => "Trust anchor does not parse",
+            Error::InvalidKey => "Signing key does not parse or does not match its DNSKEY",
+            Error::InvalidZone => "Zone cannot be signed",
+            Error::BadZoneDigest => "Zone digest is missing or does not match",
+            Error::TooManyIterations => "Too many NSEC3 iterations",
+            Error::InvalidRegexp => "NAPTR substitution expression does not parse",
+            Error::InvalidNumber => "Not an E.164 telephone number",
         }
     }
 }
 impl ::std::error::Error for Error {
-    fn cause(&self) -> Option<&dyn (::std::error::Error)> {
+    fn cause(&self) -> Option<&dyn ::std::error::Error> {
         match *self {
             Error::CString(ref e) => Some(e),
             Error::Utf8(ref e) => Some(e),
@@ -107,6 +198,17 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::Resolver(ref e) => write!(f, "{}: {:?}", self.description(), e),
+            Error::Extended {
+                ref error,
+                rcode,
+                ref extended,
+            } => {
+                write!(f, "{}: {:?} ({}", self.description(), error, rcode)?;
+                for e in extended {
+                    write!(f, ", {}", e)?;
+                }
+                write!(f, ")")
+            }
             Error::CString(ref e) => write!(
                 f,
                 "Name supplied contains a null byte at \
@@ -122,6 +224,8 @@ impl fmt::Display for Error {
             ),
             Error::Utf8(ref e) => write!(f, "{}: {}", self.description(), e),
             Error::UnknownClass(u) => write!(f, "{}: {}", self.description(), u),
+            Error::UnsupportedAlgorithm(a) => write!(f, "{}: {}", self.description(), a),
+            Error::Io(k) => write!(f, "{}: {:?}", self.description(), k),
             _ => write!(f, "{}", self.description()),
         }
     }
@@ -137,6 +241,11 @@ impl From<Utf8Error> for Error {
         Error::Utf8(err)
     }
 }
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err.kind())
+    }
+}
 impl From<FromBytesWithNulError> for Error {
     fn from(err: FromBytesWithNulError) -> Error {
         Error::CStr(err)
@@ -0,0 +1,301 @@
+//! A report on the DNSSEC state of a zone, for monitoring: when the signatures at its
+//! apex expire and which keys made them, whether the parent's DS records still lead to a
+//! current key-signing key, and whether CDS and CDNSKEY records ask the parent for a
+//! change (RFC 7344 and 8078).  Requires the `dnssec` feature.
+//!
+//! Everything is fetched through the `Resolver`, so a validating resolver that refuses
+//! bogus answers will hide some problems; configure it with the zone's own name servers
+//! to see them all.  Lookups that fail are listed in `Health::errors` rather than failing
+//! the whole report.
+
+use crate::dnssec::{self, Rrset};
+use crate::error::Error;
+use crate::record::{RecordData, RecordType, CDNSKEY, CDS, DNSKEY, DS};
+use crate::{wire, Class, Resolver, ResolverOption, Section};
+use std::str;
+use std::time::{Duration, SystemTime};
+
+// The RRsets checked.  DS records are the parent's, but are found at the same name.
+const TYPES: [RecordType; 6] = [
+    RecordType::DNSKEY,
+    RecordType::SOA,
+    RecordType::NS,
+    RecordType::CDS,
+    RecordType::CDNSKEY,
+    RecordType::DS,
+];
+
+/// A signature of an RRset at the zone apex
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub rr_type: u16,
+    pub signer_name: String,
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub inception: SystemTime,
+    pub expiration: SystemTime,
+    /// Whether a key of the zone made it.  The DS records are signed by the parent, so
+    /// their signatures are not checked.
+    pub verified: bool,
+}
+
+impl Signature {
+    /// Time left until the signature expires, or `None` if it has
+    pub fn remaining(&self) -> Option<Duration> {
+        self.expiration.duration_since(SystemTime::now()).ok()
+    }
+}
+
+/// A DS record of the parent zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelegationSigner {
+    pub ds: DS,
+    /// The tag of the key-signing key it is the digest of, if that key is published
+    /// and signs the DNSKEY RRset
+    pub key_tag: Option<u16>,
+}
+
+/// What the CDS and CDNSKEY records ask of the parent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rollover {
+    /// There are none, or they agree with the DS records in place
+    None,
+    /// The parent's DS records are to be replaced with those the CDS and CDNSKEY
+    /// records give
+    Pending,
+    /// The parent's DS records are to be removed (RFC 8078)
+    Delete,
+    /// The records fail the checks of RFC 7344 section 4.1, so a parent would ignore
+    /// them: the reason
+    Invalid(&'static str),
+}
+
+/// The DNSSEC state of a zone
+#[derive(Debug, Clone)]
+pub struct Health {
+    pub zone: String,
+    /// The zone's DNSKEY records
+    pub keys: Vec<DNSKEY>,
+    /// Signatures of the RRsets at the apex
+    pub signatures: Vec<Signature>,
+    /// RRsets at the apex without signatures, by type
+    pub unsigned: Vec<u16>,
+    pub ds: Vec<DelegationSigner>,
+    pub cds: Vec<CDS>,
+    pub cdnskey: Vec<CDNSKEY>,
+    pub rollover: Rollover,
+    /// The lookups that failed, by type; the report is made without those RRsets
+    pub errors: Vec<(u16, Error)>,
+}
+
+impl Health {
+    /// Whether one of the parent's DS records leads to a current key-signing key
+    pub fn ds_matches_ksk(&self) -> bool {
+        self.ds.iter().any(|ds| ds.key_tag.is_some())
+    }
+
+    /// The signatures that expire within `within` (or have expired), soonest first
+    pub fn expiring_within(&self, within: Duration) -> Vec<&Signature> {
+        let deadline = SystemTime::now() + within;
+        let mut expiring: Vec<&Signature> = self
+            .signatures
+            .iter()
+            .filter(|s| s.expiration <= deadline)
+            .collect();
+        expiring.sort_by_key(|s| s.expiration);
+        expiring
+    }
+
+    /// The signature that expires first
+    pub fn next_expiry(&self) -> Option<&Signature> {
+        self.signatures.iter().min_by_key(|s| s.expiration)
+    }
+}
+
+impl Resolver {
+    /// Report on the DNSSEC state of `zone`; see the `health` module.  DNSSEC records are
+    /// asked for while doing so, as the signatures are needed; the resolver's options are
+    /// then left as they were.
+    pub fn dnssec_health(&mut self, zone: &[u8]) -> Result<Health, Error> {
+        self.with_option(ResolverOption::UseDNSSEC, true, |resolver| {
+            resolver.health(zone)
+        })
+    }
+
+    fn health(&mut self, zone: &[u8]) -> Result<Health, Error> {
+        let name = str::from_utf8(zone)?;
+        let labels = wire::labels(zone)?;
+        let mut health = Health {
+            zone: name.to_owned(),
+            keys: Vec::new(),
+            signatures: Vec::new(),
+            unsigned: Vec::new(),
+            ds: Vec::new(),
+            cds: Vec::new(),
+            cdnskey: Vec::new(),
+            rollover: Rollover::None,
+            errors: Vec::new(),
+        };
+        // Keys that make current signatures of the DNSKEY RRset, and of the CDS and
+        // CDNSKEY RRsets
+        let mut key_signers: Vec<DNSKEY> = Vec::new();
+        let mut child_signers: Vec<DNSKEY> = Vec::new();
+
+        for typ in TYPES {
+            let response = self.query_response(zone, Class::IN, typ);
+            let rrsets = match response
+                .as_ref()
+                .map_err(Error::clone)
+                .and_then(|response| dnssec::rrsets(response, Section::Answer))
+            {
+                Ok(rrsets) => rrsets,
+                Err(e) => {
+                    health.errors.push((typ as u16, e));
+                    continue;
+                }
+            };
+            let rrset = match rrsets
+                .iter()
+                .find(|r| r.rr_type == typ as u16 && wire::names_equal(&r.labels, &labels))
+            {
+                Some(rrset) => rrset,
+                None => continue,
+            };
+            match typ {
+                RecordType::DNSKEY => health.keys = extract(rrset),
+                RecordType::CDS => health.cds = extract(rrset),
+                RecordType::CDNSKEY => health.cdnskey = extract(rrset),
+                RecordType::DS => {
+                    health.ds = extract(rrset)
+                        .into_iter()
+                        .map(|ds| DelegationSigner { ds, key_tag: None })
+                        .collect()
+                }
+                _ => {}
+            }
+            if rrset.rrsigs.is_empty() {
+                health.unsigned.push(typ as u16);
+            }
+            // The parent's keys are not fetched, so its signatures are not checked.
+            let keys: &[DNSKEY] = match typ {
+                RecordType::DS => &[],
+                _ => &health.keys,
+            };
+            for rrsig in &rrset.rrsigs {
+                let signer = keys
+                    .iter()
+                    .find(|key| key.is_zone_key() && rrset.is_signed_by(rrsig, key));
+                if let Some(key) = signer.filter(|_| rrsig.is_current()) {
+                    match typ {
+                        RecordType::DNSKEY => key_signers.push(key.clone()),
+                        RecordType::CDS | RecordType::CDNSKEY => child_signers.push(key.clone()),
+                        _ => {}
+                    }
+                }
+                health.signatures.push(Signature {
+                    name: rrset.name.clone(),
+                    rr_type: typ as u16,
+                    signer_name: rrsig.signer_name.clone(),
+                    key_tag: rrsig.key_tag,
+                    algorithm: rrsig.algorithm,
+                    inception: rrsig.inception_time(),
+                    expiration: rrsig.expiration_time(),
+                    verified: signer.is_some(),
+                });
+            }
+        }
+
+        for ds in &mut health.ds {
+            ds.key_tag = key_signers
+                .iter()
+                .find(|key| {
+                    key.is_secure_entry_point() && !key.is_revoked() && ds.ds.matches(name, key)
+                })
+                .map(DNSKEY::key_tag);
+        }
+        let ds: Vec<DS> = health.ds.iter().map(|d| d.ds.clone()).collect();
+        health.rollover = rollover(
+            name,
+            &health.keys,
+            &ds,
+            &health.cds,
+            &health.cdnskey,
+            &child_signers,
+        );
+        Ok(health)
+    }
+}
+
+fn extract<T: RecordData>(rrset: &Rrset) -> Vec<T> {
+    rrset
+        .rrs
+        .iter()
+        .filter_map(|rr| T::extract(rr).ok())
+        .collect()
+}
+
+/// What the CDS and CDNSKEY records of `zone` ask of the parent, given its keys, the DS
+/// records in place, and the keys that sign the CDS and CDNSKEY RRsets
+pub(crate) fn rollover(
+    zone: &str,
+    keys: &[DNSKEY],
+    ds: &[DS],
+    cds: &[CDS],
+    cdnskey: &[CDNSKEY],
+    signers: &[DNSKEY],
+) -> Rollover {
+    if cds.is_empty() && cdnskey.is_empty() {
+        return Rollover::None;
+    }
+    // Once the zone is secure, changes must come from a key the DS records lead to.
+    if !ds.is_empty()
+        && !signers
+            .iter()
+            .any(|key| ds.iter().any(|ds| ds.matches(zone, key)))
+    {
+        return Rollover::Invalid("not signed by a key the DS records lead to");
+    }
+
+    let deletes = cds.iter().filter(|c| c.is_delete()).count()
+        + cdnskey.iter().filter(|c| c.is_delete()).count();
+    if deletes == cds.len() + cdnskey.len() {
+        return Rollover::Delete;
+    } else if deletes > 0 {
+        return Rollover::Invalid("delete request mixed with other records");
+    }
+
+    if !cds
+        .iter()
+        .all(|c| keys.iter().any(|key| c.matches(zone, key)))
+    {
+        return Rollover::Invalid("CDS does not match a DNSKEY");
+    }
+    if !cdnskey.iter().all(|c| keys.contains(c)) {
+        return Rollover::Invalid("CDNSKEY is not in the DNSKEY RRset");
+    }
+    let agree = cdnskey
+        .iter()
+        .all(|k| cds.iter().any(|c| c.matches(zone, k)))
+        && cds
+            .iter()
+            .all(|c| cdnskey.iter().any(|k| c.matches(zone, k)));
+    if !cds.is_empty() && !cdnskey.is_empty() && !agree {
+        return Rollover::Invalid("CDS and CDNSKEY disagree");
+    }
+
+    let current = if cds.is_empty() {
+        ds.iter()
+            .all(|d| cdnskey.iter().any(|k| d.matches(zone, k)))
+            && cdnskey
+                .iter()
+                .all(|k| ds.iter().any(|d| d.matches(zone, k)))
+    } else {
+        ds.iter().all(|d| cds.iter().any(|c| **c == *d)) && cds.iter().all(|c| ds.contains(c))
+    };
+    if current {
+        Rollover::None
+    } else {
+        Rollover::Pending
+    }
+}
@@ -7,6 +7,9 @@
 //! using the newer thread-safe function calls.  It may not work on older version of glibc, or
 //! on other operating systems.  Pull-requests which improve portability are appreciated.
 //!
+//! Alternatively, the `native` feature provides a backend that sends queries itself and
+//! needs neither glibc nor bindgen; see the `backend` module.
+//!
 //! # Example
 //!
 //! ````
@@ -20,8 +23,8 @@
 //!     let mut resolver = Resolver::new().unwrap();
 //!
 //!     // .query() and .search() are the main interfaces to the resolver.
-//!     let mut response = resolver.query(b"gmail.com", Class::IN,
-//!                                       RecordType::MX).unwrap();
+//!     let response = resolver.query(b"gmail.com", Class::IN,
+//!                                   RecordType::MX).unwrap();
 //!
 //!     // .get_section_count() returns the number of records in that
 //!     // section of the response.  There are four sections, but we
@@ -40,59 +43,308 @@
 //! ````
 
 extern crate byteorder;
+#[cfg(feature = "glibc")]
+extern crate libc;
+#[cfg(feature = "glibc")]
 extern crate libresolv_sys;
+#[cfg(feature = "dnssec")]
+extern crate ring;
 
 pub mod error;
 use error::{Error, ResolutionError};
 
 mod response;
-pub use response::{Flags, RecordItems, Response, Section};
+pub use response::{Flags, Opcode, Rcode, RecordItems, Response, ResponseKind, Section};
 
 pub mod record;
 pub use record::{Class, Record, RecordType};
 
+mod option;
+pub use option::ResolverOption;
+
+pub mod edns;
+pub use edns::{ClientSubnet, Edns, EdnsOption};
+
+pub mod cookie;
+pub use cookie::Cookies;
+
+pub mod config;
+pub use config::ResolverConfig;
+
+pub mod backend;
+pub use backend::{Backend, BackendKind};
+
+mod watch;
+use watch::ConfigWatch;
+
+mod pool;
+pub use pool::{PooledResolver, ResolverPool};
+
+pub mod cache;
+pub use cache::{CacheStats, CachingResolver};
+
+pub mod chain;
+pub use chain::{Alias, Resolution};
+
+pub mod svcb;
+
+pub mod naptr;
+
+pub mod validate;
+
+#[cfg(feature = "dnssec")]
+pub mod denial;
+#[cfg(feature = "dnssec")]
+pub mod dnssec;
+#[cfg(feature = "dnssec")]
+pub mod health;
+#[cfg(feature = "dnssec")]
+pub mod sign;
+#[cfg(feature = "dnssec")]
+pub mod zonemd;
+
+pub mod transport;
+pub use transport::Transport;
+
+mod wire;
+
+#[cfg(feature = "tokio")]
+mod async_resolver;
+#[cfg(feature = "tokio")]
+pub use async_resolver::AsyncResolver;
+
 #[cfg(test)]
 mod tests;
 
-use std::ffi::CString;
-use std::ops::{Deref, DerefMut};
-
-type Context = libresolv_sys::__res_state;
+use std::path::{Path, PathBuf};
 
-pub use libresolv_sys::ResolverOption;
+/// Where a resolver's configuration came from, so that it can be initialised again.
+#[derive(Debug, Clone)]
+enum Source {
+    /// The system configuration, `/etc/resolv.conf`
+    System,
+    /// A configuration supplied by the caller
+    Config(ResolverConfig),
+    /// A configuration file in `resolv.conf` syntax
+    Path(PathBuf),
+}
 
 pub struct Resolver {
-    context: Context,
+    backend: Box<dyn Backend>,
+    kind: BackendKind,
+    source: Source,
+    // The configuration in use, unless glibc read it itself
+    config: Option<ResolverConfig>,
+    // Options set by the caller, re-applied whenever the backend is re-initialised
+    options: Vec<(ResolverOption, bool)>,
+    edns_options: Vec<EdnsOption>,
+    cookies: Option<Cookies>,
+    watch: Option<ConfigWatch>,
+    // Set when a query failed in a way that may have left the backend unusable
+    failed: bool,
 }
 
 impl Resolver {
     pub fn new() -> Option<Resolver> {
+        Resolver::from_source(BackendKind::default(), Source::System)
+    }
+
+    /// Create a resolver from the given configuration rather than from the system's
+    /// `/etc/resolv.conf`.  With the glibc backend, at most three name servers are used.
+    /// If none are given the local host is queried, as glibc does.
+    pub fn from_config(config: &ResolverConfig) -> Option<Resolver> {
+        Resolver::from_source(BackendKind::default(), Source::Config(config.clone()))
+    }
+
+    /// Create a resolver from a configuration file in `resolv.conf` syntax, honouring the
+    /// `LOCALDOMAIN` and `RES_OPTIONS` environment variables.  Unlike `from_config()`, the
+    /// file can be re-read when it changes; see `reload_on_change()`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Resolver> {
+        Resolver::from_source(
+            BackendKind::default(),
+            Source::Path(path.as_ref().to_path_buf()),
+        )
+    }
+
+    /// Create a resolver using a particular backend, configured from `config` or, if there
+    /// is none, from the system configuration.
+    pub fn with_backend(kind: BackendKind, config: Option<&ResolverConfig>) -> Option<Resolver> {
+        let source = match config {
+            Some(config) => Source::Config(config.clone()),
+            None => Source::System,
+        };
+        Resolver::from_source(kind, source)
+    }
+
+    fn from_source(kind: BackendKind, source: Source) -> Option<Resolver> {
+        let (backend, config) = Resolver::create(kind, &source)?;
         let mut resolver = Resolver {
-            context: libresolv_sys::__res_state::default(),
+            backend,
+            kind,
+            source,
+            config,
+            options: Vec::new(),
+            edns_options: Vec::new(),
+            cookies: None,
+            watch: None,
+            failed: false,
+        };
+        resolver.set_option(ResolverOption::Default, true);
+        Some(resolver)
+    }
+
+    fn create(
+        kind: BackendKind,
+        source: &Source,
+    ) -> Option<(Box<dyn Backend>, Option<ResolverConfig>)> {
+        let config = match *source {
+            Source::System => None,
+            Source::Config(ref config) => Some(config.clone()),
+            Source::Path(ref path) => Some(ResolverConfig::from_path(path).ok()?),
         };
+        let backend = kind.create(config.as_ref())?;
+        Some((backend, config))
+    }
 
-        if unsafe { libresolv_sys::res_ninit(&mut resolver.context) } != 0 {
-            return None;
+    // Another resolver configured the same way, with the same options
+    pub(crate) fn duplicate(&self) -> Option<Resolver> {
+        let mut resolver = Resolver::from_source(self.kind, self.source.clone())?;
+        for &(option, value) in &self.options {
+            resolver.option(option, value);
         }
+        resolver.set_edns_options(self.edns_options.clone());
+        resolver.set_cookies(self.cookies.clone());
+        Some(resolver)
+    }
 
-        resolver.option(ResolverOption::Default, true);
+    /// The backend this resolver uses
+    pub fn backend(&self) -> BackendKind {
+        self.kind
+    }
 
-        Some(resolver)
+    /// The configuration in use, if it was given or read by this crate rather than by
+    /// glibc
+    pub fn config(&self) -> Option<&ResolverConfig> {
+        self.config.as_ref()
     }
 
     /// Set or unset an option
     pub fn option(&mut self, option: ResolverOption, value: bool) {
-        if value {
-            self.context.options = self.context.options | (option as u64);
-        } else {
-            self.context.options = self.context.options & !(option as u64);
+        self.options.retain(|&(o, _)| o != option);
+        self.options.push((option, value));
+        self.set_option(option, value);
+    }
+
+    fn set_option(&mut self, option: ResolverOption, value: bool) {
+        self.backend.set_option(option, value);
+    }
+
+    // Run `f` with `option` set to `value`, and then set it back as it was
+    #[cfg(feature = "dnssec")]
+    pub(crate) fn with_option<T, F>(&mut self, option: ResolverOption, value: bool, f: F) -> T
+    where
+        F: FnOnce(&mut Resolver) -> T,
+    {
+        let options = self.options.clone();
+        let was = self.backend.has_option(option);
+        self.option(option, value);
+        let result = f(self);
+        self.options = options;
+        self.set_option(option, was);
+        result
+    }
+
+    /// Send these EDNS options with each query, replacing any set before.  See the
+    /// `edns` module.
+    pub fn set_edns_options(&mut self, options: Vec<EdnsOption>) {
+        self.backend.set_edns_options(&options);
+        self.edns_options = options;
+    }
+
+    /// Send DNS cookies made with `cookies` (RFC 7873), or none.  See the `cookie` module.
+    pub fn set_cookies(&mut self, cookies: Option<Cookies>) {
+        self.backend.set_cookies(cookies.clone());
+        self.cookies = cookies;
+    }
+
+    // The client subnet sent with each query, if one was set with `set_edns_options()`
+    pub(crate) fn client_subnet(&self) -> Option<&ClientSubnet> {
+        self.edns_options.iter().find_map(|o| match *o {
+            EdnsOption::ClientSubnet(ref subnet) => Some(subnet),
+            _ => None,
+        })
+    }
+
+    /// Re-read the configuration file before each query and re-initialise the resolver
+    /// if it has changed.
+    ///
+    /// glibc only reloads `/etc/resolv.conf` automatically for its global resolver state;
+    /// a `Resolver` otherwise keeps the configuration it was created with for its whole
+    /// life.  This applies to resolvers created with `new()` (watching `/etc/resolv.conf`)
+    /// and `from_path()` (watching that file).  It has no effect on resolvers created with
+    /// `from_config()`, which have no file to watch.
+    pub fn reload_on_change(&mut self, enable: bool) {
+        self.watch = match (enable, &self.source) {
+            (false, _) | (true, Source::Config(_)) => None,
+            (true, Source::System) => Some(ConfigWatch::new(config::RESOLV_CONF)),
+            (true, Source::Path(path)) => Some(ConfigWatch::new(path)),
+        };
+    }
+
+    /// Discard the resolver state and initialise it again from its configuration,
+    /// re-reading the configuration file if there is one.  Options set with `option()`
+    /// are kept.  On failure, the previous state is kept and `false` is returned.
+    pub fn reinit(&mut self) -> bool {
+        let (backend, config) = match Resolver::create(self.kind, &self.source) {
+            Some(created) => created,
+            None => return false,
+        };
+        self.backend = backend;
+        self.config = config;
+        self.failed = false;
+        self.set_option(ResolverOption::Default, true);
+        for (option, value) in self.options.clone() {
+            self.set_option(option, value);
+        }
+        self.backend.set_edns_options(&self.edns_options);
+        self.backend.set_cookies(self.cookies.clone());
+        true
+    }
+
+    // Return to the state the resolver was created in, dropping the options, EDNS
+    // options, cookies and configuration watch set since.  Returns `false` if it had to
+    // be re-initialised and that failed.
+    pub(crate) fn reset(&mut self) -> bool {
+        let changed = !self.options.is_empty()
+            || !self.edns_options.is_empty()
+            || self.cookies.is_some()
+            || self.watch.is_some();
+        if !changed && !self.failed {
+            return true;
+        }
+        self.options.clear();
+        self.edns_options.clear();
+        self.cookies = None;
+        self.watch = None;
+        self.reinit()
+    }
+
+    fn check_reload(&mut self) {
+        let changed = match self.watch {
+            Some(ref mut watch) => watch.poll(),
+            None => false,
+        };
+        if changed && !self.reinit() {
+            if let Some(ref mut watch) = self.watch {
+                watch.forget();
+            }
         }
     }
 
     /// Lookup the record.  Applies the search algorithm to the domain name given
     /// (if not fully qualified, it completes it using rules specified in `resolv.conf`
-    /// search entries).  In addition, this also searches your hosts file.  Applies
-    /// recursion if available and not turned off (it is on by default).
+    /// search entries).  In addition, this also searches your hosts file (glibc backend
+    /// only).  Applies recursion if available and not turned off (it is on by default).
     ///
     /// This is the highest level resolver routine, and is the one called by
     /// gethostbyname.
@@ -102,35 +354,9 @@ impl Resolver {
         class: Class,
         typ: RecordType,
     ) -> Result<Response, Error> {
-        let name = match CString::new(name) {
-            Ok(c) => c,
-            Err(n) => return Err(Error::CString(n)),
-        };
-        let buflen: usize = libresolv_sys::NS_PACKETSZ as usize;
-        let mut buffer: Box<Vec<u8>> = Box::new(Vec::with_capacity(buflen));
-
-        let rlen: i32 = unsafe {
-            libresolv_sys::res_nsearch(
-                &mut self.context,
-                name.as_ptr(),
-                class as i32,
-                typ as i32,
-                buffer.deref_mut().as_mut_ptr(),
-                buflen as i32,
-            )
-        };
-        if rlen == -1 {
-            return Err(From::from(self.get_error()));
-        }
-
-        let mut msg: libresolv_sys::ns_msg = libresolv_sys::ns_msg::default();
-        unsafe {
-            if libresolv_sys::ns_initparse(buffer.deref().as_ptr(), rlen, &mut msg) < 0 {
-                return Err(Error::ParseError);
-            }
-        }
-
-        Ok(Response::new(msg, buffer))
+        self.check_reload();
+        let result = self.backend.search(name, class, typ);
+        check_answer(self.finish(result)?)
     }
 
     /// Lookup the record.  Does not apply the search algorithm, so `dname` must be a complete
@@ -142,45 +368,76 @@ impl Resolver {
         class: Class,
         typ: RecordType,
     ) -> Result<Response, Error> {
-        let name = match CString::new(dname) {
-            Ok(c) => c,
-            Err(n) => return Err(Error::CString(n)),
-        };
-        let buflen: usize = libresolv_sys::NS_PACKETSZ as usize;
-        let mut buffer: Box<Vec<u8>> = Box::new(Vec::with_capacity(buflen));
-
-        let rlen: i32 = unsafe {
-            libresolv_sys::res_nquery(
-                &mut self.context,
-                name.as_ptr(),
-                class as i32,
-                typ as i32,
-                buffer.deref_mut().as_mut_ptr(),
-                buflen as i32,
-            )
-        };
-        if rlen == -1 {
-            return Err(From::from(self.get_error()));
-        }
+        check_answer(self.query_response(dname, class, typ)?)
+    }
 
-        let mut msg: libresolv_sys::ns_msg = libresolv_sys::ns_msg::default();
-        unsafe {
-            if libresolv_sys::ns_initparse(buffer.deref().as_ptr(), rlen, &mut msg) < 0 {
-                return Err(Error::ParseError);
-            }
-        }
+    /// As `query()`, but on behalf of clients in `subnet` (RFC 7871), in place of any
+    /// client subnet set with `set_edns_options()`.  `Response::client_subnet()` gives
+    /// the scope of the answer.
+    pub fn query_with_subnet(
+        &mut self,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+        subnet: &ClientSubnet,
+    ) -> Result<Response, Error> {
+        check_answer(self.query_response_from(dname, class, typ, Some(subnet))?)
+    }
+
+    // As `query_response()`, with a client subnet if one is given
+    pub(crate) fn query_response_from(
+        &mut self,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+        subnet: Option<&ClientSubnet>,
+    ) -> Result<Response, Error> {
+        let subnet = match subnet {
+            Some(subnet) => subnet,
+            None => return self.query_response(dname, class, typ),
+        };
+        self.check_reload();
+        let mut options: Vec<EdnsOption> = self
+            .edns_options
+            .iter()
+            .filter(|o| o.code() != edns::OPTION_CLIENT_SUBNET)
+            .cloned()
+            .collect();
+        options.push(EdnsOption::ClientSubnet(subnet.clone()));
+        self.backend.set_edns_options(&options);
+        let result = self.backend.query(dname, class, typ);
+        self.backend.set_edns_options(&self.edns_options);
+        self.finish(result)
+    }
 
-        Ok(Response::new(msg, buffer))
+    // As `query()`, but NXDOMAIN and NODATA answers are returned rather than errors.
+    pub(crate) fn query_response(
+        &mut self,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+    ) -> Result<Response, Error> {
+        self.check_reload();
+        let result = self.backend.query(dname, class, typ);
+        self.finish(result)
     }
 
-    fn get_error(&self) -> ResolutionError {
-        match self.context.res_h_errno {
-            0 => ResolutionError::Success,
-            1 => ResolutionError::HostNotFound,
-            2 => ResolutionError::TryAgain,
-            3 => ResolutionError::NoRecovery,
-            4 => ResolutionError::NoData,
-            _ => ResolutionError::HostNotFound, // fallback
-        }
+    fn finish(&mut self, result: Result<Vec<u8>, Error>) -> Result<Response, Error> {
+        let result = result.and_then(Response::from_buffer);
+        self.failed = match result {
+            Err(Error::ParseError) => true,
+            Err(ref e) => matches!(
+                e.resolution_error(),
+                Some(ResolutionError::TryAgain) | Some(ResolutionError::NoRecovery)
+            ),
+            Ok(_) => false,
+        };
+        result
     }
 }
+
+// Report NXDOMAIN and NODATA answers as errors
+fn check_answer(response: Response) -> Result<Response, Error> {
+    edns::check_rcode(response.as_bytes())?;
+    Ok(response)
+}
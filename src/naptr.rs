@@ -0,0 +1,607 @@
+//! Dynamic Delegation Discovery (RFC 3402) with NAPTR records: finding servers with
+//! S-NAPTR (RFC 3958) and U-NAPTR (RFC 4848), and URIs for telephone numbers with ENUM
+//! (RFC 6116).
+//!
+//! Substitution expressions are POSIX extended regular expressions, matched here by
+//! backtracking, so of two matches starting at the same place the first found wins rather
+//! than the longest.  The patterns NAPTR records use in practice, anchored at both ends,
+//! match the same either way.
+
+use crate::chain::MAX_CHAIN_LEN;
+use crate::error::Error;
+use crate::record::{NAPTR, SRV};
+use crate::{wire, Class, Resolver};
+use std::cmp::Reverse;
+use std::str;
+
+/// The ENUM tree of the public telephone network
+pub const E164_ARPA: &str = "e164.arpa";
+
+/// A server for a service, found with S-NAPTR or U-NAPTR
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Service {
+    pub order: u16,
+    pub preference: u16,
+    /// The application protocols of the rule, e.g. "smtp", in lower case
+    pub protocols: Vec<String>,
+    pub target: Target,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A host whose addresses are looked up as usual, with the port if an SRV record gave
+    /// one
+    Host { name: String, port: Option<u16> },
+    /// A URI, from a U-NAPTR rule
+    Uri(String),
+}
+
+/// A URI for a telephone number, found with ENUM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumUri {
+    pub order: u16,
+    pub preference: u16,
+    /// The enumservices of the rule, e.g. "sip" or "voice:tel", in lower case
+    pub services: Vec<String>,
+    pub uri: String,
+}
+
+/// Apply a substitution expression, `!ere!replacement!` with an optional "i" flag to
+/// ignore case, to `input` (RFC 3402 section 3.2).  Any character other than a digit,
+/// a backslash or "i" may stand in for the "!".  The result is the replacement, with
+/// `\1` to `\9` standing for what the ERE's groups matched; `None` if it does not match.
+pub fn substitute(regexp: &str, input: &str) -> Result<Option<String>, Error> {
+    let mut chars = regexp.chars();
+    let delim = chars.next().ok_or(Error::InvalidRegexp)?;
+    if delim.is_ascii_digit() || delim == '\\' || delim == 'i' {
+        return Err(Error::InvalidRegexp);
+    }
+    // Escaped delimiters keep their backslash, which means the character itself in both
+    // the ERE and the replacement.
+    let mut parts = vec![String::new()];
+    let mut escaped = false;
+    for c in chars {
+        let part = parts.last_mut().ok_or(Error::InvalidRegexp)?;
+        if escaped {
+            part.push('\\');
+            part.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == delim {
+            parts.push(String::new());
+        } else {
+            part.push(c);
+        }
+    }
+    let (ere, replacement, flags) = match parts.as_slice() {
+        [ere, replacement, flags] if !escaped => (ere, replacement, flags),
+        _ => return Err(Error::InvalidRegexp),
+    };
+    let ignore_case = match flags.as_str() {
+        "" => false,
+        "i" => true,
+        _ => return Err(Error::InvalidRegexp),
+    };
+
+    let mut parser = Parser {
+        chars: ere.chars().collect(),
+        pos: 0,
+        groups: 0,
+    };
+    let node = parser.alternation()?;
+    if parser.pos < parser.chars.len() {
+        return Err(Error::InvalidRegexp);
+    }
+    let mut pieces = Vec::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            pieces.push(Piece::Char(c));
+            continue;
+        }
+        match chars.next() {
+            Some(d @ '1'..='9') => {
+                let group = d as usize - '0' as usize;
+                if group > parser.groups {
+                    return Err(Error::InvalidRegexp);
+                }
+                pieces.push(Piece::Group(group));
+            }
+            Some(c) => pieces.push(Piece::Char(c)),
+            None => return Err(Error::InvalidRegexp),
+        }
+    }
+
+    let text: Vec<char> = input.chars().collect();
+    let matcher = Matcher {
+        text: &text,
+        ignore_case,
+    };
+    Ok(matcher.captures(&node, parser.groups).map(|groups| {
+        pieces
+            .iter()
+            .map(|piece| match *piece {
+                Piece::Char(c) => c.to_string(),
+                Piece::Group(i) => groups[i]
+                    .map(|(start, end)| text[start..end].iter().collect())
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }))
+}
+
+/// The ENUM domain of `number`, an E.164 number such as "+44 20 7946 0123", under `apex`,
+/// e.g. `E164_ARPA` (RFC 6116 section 2.4)
+pub fn enum_domain(number: &str, apex: &str) -> Result<String, Error> {
+    let mut domain = String::new();
+    for digit in e164(number)?[1..].chars().rev() {
+        domain.push(digit);
+        domain.push('.');
+    }
+    domain.push_str(apex);
+    Ok(domain)
+}
+
+// `number` as "+" and its digits, without spaces, hyphens, dots or parentheses
+fn e164(number: &str) -> Result<String, Error> {
+    let digits = number
+        .trim()
+        .strip_prefix('+')
+        .ok_or(Error::InvalidNumber)?
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'));
+    let mut out = String::from("+");
+    for c in digits {
+        if !c.is_ascii_digit() {
+            return Err(Error::InvalidNumber);
+        }
+        out.push(c);
+    }
+    // E.164 numbers have at most 15 digits.
+    if out.len() < 2 || out.len() > 16 {
+        return Err(Error::InvalidNumber);
+    }
+    Ok(out)
+}
+
+// The protocols of an S-NAPTR or U-NAPTR rule for `service`, e.g. "RELAY:smtp:lmtp"
+fn protocols(naptr: &NAPTR, service: &str) -> Option<Vec<String>> {
+    let mut fields = naptr.services.split(':');
+    if !fields.next()?.eq_ignore_ascii_case(service) {
+        return None;
+    }
+    Some(fields.map(str::to_ascii_lowercase).collect())
+}
+
+// The enumservices of an ENUM rule, e.g. "E2U+sip" or "E2U+voice:tel+sms:tel"
+fn enumservices(naptr: &NAPTR) -> Option<Vec<String>> {
+    let mut fields = naptr.services.split('+');
+    if !fields.next()?.eq_ignore_ascii_case("E2U") {
+        return None;
+    }
+    let services: Vec<String> = fields.map(str::to_ascii_lowercase).collect();
+    if services.is_empty() || services.iter().any(String::is_empty) {
+        return None;
+    }
+    Some(services)
+}
+
+impl Resolver {
+    /// Find servers for `service`, an application service tag such as "RELAY", in
+    /// `domain` with S-NAPTR (RFC 3958).  Rules with the "S" flag lead to the targets of
+    /// SRV records, lowest priority and then highest weight first, and rules with the
+    /// "A" flag to a host on the protocol's default port.  Non-terminal rules are followed.
+    /// The servers are in the order to try them; empty if `domain` has no rules for
+    /// `service`.
+    pub fn s_naptr(&mut self, domain: &[u8], service: &str) -> Result<Vec<Service>, Error> {
+        self.locate(domain, service, false)
+    }
+
+    /// As `s_naptr()`, with U-NAPTR (RFC 4848): rules may also have the "U" flag, whose
+    /// substitution expression, applied to `domain`, gives a URI.
+    pub fn u_naptr(&mut self, domain: &[u8], service: &str) -> Result<Vec<Service>, Error> {
+        self.locate(domain, service, true)
+    }
+
+    /// The URIs for `number`, an E.164 number such as "+44 20 7946 0123", from the ENUM
+    /// tree at `apex`, usually `E164_ARPA` (RFC 6116).  The URIs are in the order to try
+    /// them; empty if the number is not in the tree.
+    pub fn enum_lookup(&mut self, number: &str, apex: &str) -> Result<Vec<EnumUri>, Error> {
+        let number = e164(number)?;
+        let domain = enum_domain(&number, apex)?;
+        let mut uris = Vec::new();
+        for (naptr, services) in self.rules(&domain, &number, &enumservices, &mut Vec::new())? {
+            if naptr.flags.eq_ignore_ascii_case("u") {
+                if let Ok(Some(uri)) = substitute(&naptr.regexp, &number) {
+                    uris.push(EnumUri {
+                        order: naptr.order,
+                        preference: naptr.preference,
+                        services,
+                        uri,
+                    });
+                }
+            }
+        }
+        Ok(uris)
+    }
+
+    fn locate(&mut self, domain: &[u8], service: &str, uris: bool) -> Result<Vec<Service>, Error> {
+        let domain = str::from_utf8(domain)?;
+        let accept = |naptr: &NAPTR| protocols(naptr, service);
+        let mut services = Vec::new();
+        for (naptr, protocols) in self.rules(domain, domain, &accept, &mut Vec::new())? {
+            let mut add = |target| {
+                services.push(Service {
+                    order: naptr.order,
+                    preference: naptr.preference,
+                    protocols: protocols.clone(),
+                    target,
+                })
+            };
+            match naptr.flags.to_ascii_lowercase().as_str() {
+                "s" => {
+                    let mut srvs: Vec<SRV> = match self
+                        .resolve::<SRV>(naptr.replacement.as_bytes(), Class::IN)
+                    {
+                        Ok(resolution) => resolution.records.into_iter().map(|r| r.data).collect(),
+                        Err(ref e) if wire::is_negative(e) => continue,
+                        Err(e) => return Err(e),
+                    };
+                    srvs.sort_by_key(|srv| (srv.priority, Reverse(srv.weight)));
+                    for srv in srvs.into_iter().filter(|srv| srv.name != ".") {
+                        add(Target::Host {
+                            name: srv.name,
+                            port: Some(srv.port),
+                        });
+                    }
+                }
+                "a" => add(Target::Host {
+                    name: naptr.replacement.clone(),
+                    port: None,
+                }),
+                "u" if uris => {
+                    if let Ok(Some(uri)) = substitute(&naptr.regexp, domain) {
+                        add(Target::Uri(uri));
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(services)
+    }
+
+    // The terminal rules `accept` takes at `name`, with what it gives for them, in order.
+    // Non-terminal rules without services, or whose services `accept` takes, are followed
+    // where they lead, to the replacement or to their expression applied to `aus`, the
+    // application's string.  Rules with expressions that do not parse are skipped, as
+    // are names already looked up.
+    fn rules<T>(
+        &mut self,
+        name: &str,
+        aus: &str,
+        accept: &dyn Fn(&NAPTR) -> Option<T>,
+        seen: &mut Vec<Vec<Vec<u8>>>,
+    ) -> Result<Vec<(NAPTR, T)>, Error> {
+        let labels = wire::labels(name.as_bytes())?;
+        if seen.iter().any(|s| wire::names_equal(s, &labels)) {
+            return Ok(Vec::new());
+        }
+        if seen.len() >= MAX_CHAIN_LEN {
+            return Err(Error::AliasChainTooLong);
+        }
+        seen.push(labels);
+
+        let mut rrset: Vec<NAPTR> = match self.resolve::<NAPTR>(name.as_bytes(), Class::IN) {
+            Ok(resolution) => resolution.records.into_iter().map(|r| r.data).collect(),
+            Err(ref e) if wire::is_negative(e) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        rrset.sort_by_key(|naptr| (naptr.order, naptr.preference));
+        let mut rules = Vec::new();
+        for naptr in rrset {
+            if naptr.is_terminal() {
+                if let Some(value) = accept(&naptr) {
+                    rules.push((naptr, value));
+                }
+                continue;
+            }
+            if !naptr.services.is_empty() && accept(&naptr).is_none() {
+                continue;
+            }
+            let next = if naptr.regexp.is_empty() {
+                Some(naptr.replacement)
+            } else {
+                substitute(&naptr.regexp, aus).ok().flatten()
+            };
+            if let Some(next) = next.filter(|next| next != ".") {
+                rules.extend(self.rules(&next, aus, accept, seen)?);
+            }
+        }
+        Ok(rules)
+    }
+}
+
+enum Piece {
+    Char(char),
+    Group(usize),
+}
+
+// What each group matched, by index in the text; group 0 is the whole match
+type Groups = Vec<Option<(usize, usize)>>;
+
+enum Node {
+    Char(char),
+    Any,
+    Set(bool, Vec<SetItem>),
+    Start,
+    End,
+    Group(Box<Node>, usize),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat(Box<Node>, u32, Option<u32>),
+}
+
+enum SetItem {
+    Range(char, char),
+    Class(fn(&char) -> bool),
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<char, Error> {
+        let c = self.peek().ok_or(Error::InvalidRegexp)?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn alternation(&mut self) -> Result<Node, Error> {
+        let mut branches = vec![self.concat()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.concat()?);
+        }
+        Ok(match branches.len() {
+            1 => branches.remove(0),
+            _ => Node::Alt(branches),
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, Error> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek().filter(|&c| c != '|' && c != ')') {
+            self.pos += 1;
+            let atom = match c {
+                '(' => {
+                    self.groups += 1;
+                    let group = self.groups;
+                    let inner = self.alternation()?;
+                    if self.next()? != ')' {
+                        return Err(Error::InvalidRegexp);
+                    }
+                    Node::Group(Box::new(inner), group)
+                }
+                '.' => Node::Any,
+                '^' => Node::Start,
+                '$' => Node::End,
+                '[' => self.set()?,
+                '\\' => Node::Char(self.next()?),
+                '*' | '+' | '?' | '{' => return Err(Error::InvalidRegexp),
+                c => Node::Char(c),
+            };
+            nodes.push(self.repeats(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn repeats(&mut self, mut atom: Node) -> Result<Node, Error> {
+        while let Some(c) = self.peek().filter(|c| "*+?{".contains(*c)) {
+            self.pos += 1;
+            let (min, max) = match c {
+                '*' => (0, None),
+                '+' => (1, None),
+                '?' => (0, Some(1)),
+                _ => {
+                    let min = self.number().ok_or(Error::InvalidRegexp)?;
+                    let max = if self.peek() == Some(',') {
+                        self.pos += 1;
+                        self.number()
+                    } else {
+                        Some(min)
+                    };
+                    if self.next()? != '}' || max.is_some_and(|max| max < min) {
+                        return Err(Error::InvalidRegexp);
+                    }
+                    (min, max)
+                }
+            };
+            atom = Node::Repeat(Box::new(atom), min, max);
+        }
+        Ok(atom)
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    // A bracket expression, after its "["
+    fn set(&mut self) -> Result<Node, Error> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut items = Vec::new();
+        loop {
+            let c = self.next()?;
+            if c == ']' && !items.is_empty() {
+                break;
+            }
+            if c == '[' && self.peek() == Some(':') {
+                self.pos += 1;
+                let start = self.pos;
+                while self.next()? != ':' {}
+                let name: String = self.chars[start..self.pos - 1].iter().collect();
+                if self.next()? != ']' {
+                    return Err(Error::InvalidRegexp);
+                }
+                let class: fn(&char) -> bool = match name.as_str() {
+                    "alpha" => char::is_ascii_alphabetic,
+                    "digit" => char::is_ascii_digit,
+                    "alnum" => char::is_ascii_alphanumeric,
+                    "upper" => char::is_ascii_uppercase,
+                    "lower" => char::is_ascii_lowercase,
+                    "space" => char::is_ascii_whitespace,
+                    "xdigit" => char::is_ascii_hexdigit,
+                    "punct" => char::is_ascii_punctuation,
+                    "cntrl" => char::is_ascii_control,
+                    "graph" => char::is_ascii_graphic,
+                    "print" => |c| c.is_ascii_graphic() || *c == ' ',
+                    "blank" => |c| *c == ' ' || *c == '\t',
+                    _ => return Err(Error::InvalidRegexp),
+                };
+                items.push(SetItem::Class(class));
+            } else if self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']') {
+                self.pos += 1;
+                let high = self.next()?;
+                if high < c {
+                    return Err(Error::InvalidRegexp);
+                }
+                items.push(SetItem::Range(c, high));
+            } else {
+                items.push(SetItem::Range(c, c));
+            }
+        }
+        Ok(Node::Set(negated, items))
+    }
+}
+
+struct Matcher<'a> {
+    text: &'a [char],
+    ignore_case: bool,
+}
+
+impl<'a> Matcher<'a> {
+    // The groups of the leftmost match of `node`, which has `groups` groups
+    fn captures(&self, node: &Node, groups: usize) -> Option<Groups> {
+        for start in 0..=self.text.len() {
+            let mut found = vec![None; groups + 1];
+            let mut end = start;
+            if self.walk(node, start, &mut found, &mut |pos, _| {
+                end = pos;
+                true
+            }) {
+                found[0] = Some((start, end));
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    // Match `node` at `pos`, then whatever follows with `next`, backtracking until it
+    // succeeds
+    fn walk(
+        &self,
+        node: &Node,
+        pos: usize,
+        groups: &mut Groups,
+        next: &mut dyn FnMut(usize, &mut Groups) -> bool,
+    ) -> bool {
+        let c = self.text.get(pos).copied();
+        match node {
+            Node::Char(expected) => {
+                c.is_some_and(|c| self.same(c, *expected)) && next(pos + 1, groups)
+            }
+            Node::Any => c.is_some() && next(pos + 1, groups),
+            Node::Set(negated, items) => {
+                c.is_some_and(|c| self.in_set(c, items) != *negated) && next(pos + 1, groups)
+            }
+            Node::Start => pos == 0 && next(pos, groups),
+            Node::End => c.is_none() && next(pos, groups),
+            Node::Group(inner, i) => self.walk(inner, pos, groups, &mut |end, groups| {
+                let old = groups[*i];
+                groups[*i] = Some((pos, end));
+                next(end, groups) || {
+                    groups[*i] = old;
+                    false
+                }
+            }),
+            Node::Concat(nodes) => self.sequence(nodes, pos, groups, next),
+            Node::Alt(branches) => branches
+                .iter()
+                .any(|branch| self.walk(branch, pos, groups, &mut *next)),
+            Node::Repeat(inner, min, max) => self.repeat(inner, (*min, *max), 0, pos, groups, next),
+        }
+    }
+
+    fn sequence(
+        &self,
+        nodes: &[Node],
+        pos: usize,
+        groups: &mut Groups,
+        next: &mut dyn FnMut(usize, &mut Groups) -> bool,
+    ) -> bool {
+        match nodes.split_first() {
+            None => next(pos, groups),
+            Some((first, rest)) => self.walk(first, pos, groups, &mut |pos, groups| {
+                self.sequence(rest, pos, groups, &mut *next)
+            }),
+        }
+    }
+
+    // Greedily: as many more times as will match, then fewer.  A repetition that matches
+    // nothing ends the loop once there have been enough.
+    fn repeat(
+        &self,
+        inner: &Node,
+        (min, max): (u32, Option<u32>),
+        count: u32,
+        pos: usize,
+        groups: &mut Groups,
+        next: &mut dyn FnMut(usize, &mut Groups) -> bool,
+    ) -> bool {
+        if max.is_none_or(|max| count < max)
+            && self.walk(inner, pos, groups, &mut |end, groups| {
+                (end > pos || count < min)
+                    && self.repeat(inner, (min, max), count + 1, end, groups, &mut *next)
+            })
+        {
+            return true;
+        }
+        count >= min && next(pos, groups)
+    }
+
+    fn same(&self, a: char, b: char) -> bool {
+        a == b || self.ignore_case && a.eq_ignore_ascii_case(&b)
+    }
+
+    fn in_set(&self, c: char, items: &[SetItem]) -> bool {
+        let mut variants = vec![c];
+        if self.ignore_case {
+            variants.push(c.to_ascii_lowercase());
+            variants.push(c.to_ascii_uppercase());
+        }
+        variants.iter().any(|c| {
+            items.iter().any(|item| match *item {
+                SetItem::Range(low, high) => low <= *c && *c <= high,
+                SetItem::Class(class) => class(c),
+            })
+        })
+    }
+}
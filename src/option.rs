@@ -0,0 +1,42 @@
+/// Options for the Resolver.  The values are glibc's `RES_*` flags, and the native backend
+/// interprets them the same way.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResolverOption {
+    /// address initialized
+    Init = 0x0000_0001,
+    /// print debug messages
+    Debug = 0x0000_0002,
+    /// use virtual circuit
+    UseVC = 0x0000_0008,
+    /// ignore truncation errors
+    IgnTc = 0x0000_0020,
+    /// recursion desired
+    Recurse = 0x0000_0040,
+    /// use default domain name
+    DefNames = 0x0000_0080,
+    /// Keep TCP socket open
+    StayOpen = 0x0000_0100,
+    /// search up local domain tree
+    DNSrch = 0x0000_0200,
+    /// shuts off HOSTALIASES feature
+    NoAliases = 0x0000_1000,
+    /// rotate ns list after each query
+    Rotate = 0x0000_4000,
+    /// Use EDNS0.
+    UseEDNS0 = 0x0010_0000,
+    /// one outstanding request at a time
+    SngLkup = 0x0020_0000,
+    /// one outstanding request at a time, but open new socket for each request
+    SngLkupReop = 0x0040_0000,
+    /// use DNSSEC using OK bit in OPT
+    UseDNSSEC = 0x0080_0000,
+    /// Do not look up unqualified name as a TLD.
+    NoTLDQuery = 0x0100_0000,
+    /// No automatic configuration reload (since glibc 2.26; invalid in prior versions)
+    NoReload = 0x0200_0000,
+    /// Request AD bit, keep it in responses (since glibc 2.31; invalid in prior version)
+    TrustAD = 0x0400_0000,
+    /// Default values
+    Default = 0x0000_02c0,
+}
@@ -0,0 +1,180 @@
+//! A pool of resolvers for multi-threaded programs.
+//!
+//! A `Resolver` may be moved between threads but not shared, and creating one reads and
+//! parses the configuration.  A `ResolverPool` keeps up to a fixed number of resolvers,
+//! all configured alike, and lends them out to whichever thread asks.
+
+use crate::{Resolver, ResolverConfig};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+struct State {
+    idle: Vec<Resolver>,
+    // Resolvers in existence, idle or lent out
+    created: usize,
+}
+
+struct Inner {
+    config: Option<ResolverConfig>,
+    size: usize,
+    state: Mutex<State>,
+    returned: Condvar,
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A panic while holding the lock cannot leave the state inconsistent.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn create(&self) -> Option<Resolver> {
+        match self.config {
+            Some(ref config) => Resolver::from_config(config),
+            None => Resolver::new(),
+        }
+    }
+}
+
+/// A pool of up to `size` resolvers.  Cloning the pool is cheap and gives another handle
+/// to the same resolvers.
+#[derive(Clone)]
+pub struct ResolverPool {
+    inner: Arc<Inner>,
+}
+
+impl ResolverPool {
+    /// Create a pool of resolvers configured from `/etc/resolv.conf`
+    pub fn new(size: usize) -> ResolverPool {
+        ResolverPool::with_config(None, size)
+    }
+
+    /// Create a pool of resolvers configured from `config`
+    pub fn from_config(config: ResolverConfig, size: usize) -> ResolverPool {
+        ResolverPool::with_config(Some(config), size)
+    }
+
+    fn with_config(config: Option<ResolverConfig>, size: usize) -> ResolverPool {
+        ResolverPool {
+            inner: Arc::new(Inner {
+                config,
+                size: size.max(1),
+                state: Mutex::new(State {
+                    idle: Vec::new(),
+                    created: 0,
+                }),
+                returned: Condvar::new(),
+            }),
+        }
+    }
+
+    /// The most resolvers this pool will create
+    pub fn size(&self) -> usize {
+        self.inner.size
+    }
+
+    /// Borrow a resolver, waiting for one to be returned if all are in use.  Resolvers are
+    /// created as needed; returns `None` if that fails.
+    pub fn get(&self) -> Option<PooledResolver> {
+        let mut state = self.inner.lock();
+        loop {
+            if let Some(resolver) = state.idle.pop() {
+                return Some(self.lend(resolver));
+            }
+            if state.created < self.inner.size {
+                state.created += 1;
+                drop(state);
+                return self.create();
+            }
+            state = self
+                .inner
+                .returned
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Borrow a resolver if one is idle or can be created, without waiting
+    pub fn try_get(&self) -> Option<PooledResolver> {
+        let mut state = self.inner.lock();
+        if let Some(resolver) = state.idle.pop() {
+            return Some(self.lend(resolver));
+        }
+        if state.created < self.inner.size {
+            state.created += 1;
+            drop(state);
+            return self.create();
+        }
+        None
+    }
+
+    // Called with a slot already reserved in `created`
+    fn create(&self) -> Option<PooledResolver> {
+        match self.inner.create() {
+            Some(resolver) => Some(self.lend(resolver)),
+            None => {
+                self.inner.lock().created -= 1;
+                self.inner.returned.notify_one();
+                None
+            }
+        }
+    }
+
+    fn lend(&self, resolver: Resolver) -> PooledResolver {
+        PooledResolver {
+            resolver: Some(resolver),
+            pool: self.inner.clone(),
+        }
+    }
+}
+
+/// A resolver borrowed from a `ResolverPool`.  It is returned to the pool when dropped.
+/// If options, EDNS options, cookies or reloading were set on it, or a query through it
+/// failed in a way that may have left it unusable, it is re-initialised first as the
+/// pool configures it, or discarded if that fails.
+pub struct PooledResolver {
+    resolver: Option<Resolver>,
+    pool: Arc<Inner>,
+}
+
+impl PooledResolver {
+    /// Take the resolver out of the pool for good.  The pool may create another in its
+    /// place.
+    pub fn detach(mut self) -> Resolver {
+        let resolver = self.resolver.take().unwrap();
+        self.pool.lock().created -= 1;
+        self.pool.returned.notify_one();
+        resolver
+    }
+}
+
+impl Deref for PooledResolver {
+    type Target = Resolver;
+
+    fn deref(&self) -> &Resolver {
+        self.resolver.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledResolver {
+    fn deref_mut(&mut self) -> &mut Resolver {
+        self.resolver.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledResolver {
+    fn drop(&mut self) {
+        let mut resolver = match self.resolver.take() {
+            Some(r) => r,
+            None => return,
+        };
+        let usable = resolver.reset();
+        let mut state = self.pool.lock();
+        if usable {
+            state.idle.push(resolver);
+        } else {
+            state.created -= 1;
+        }
+        drop(state);
+        self.pool.returned.notify_one();
+    }
+}
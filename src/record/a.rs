@@ -1,9 +1,6 @@
-use super::{RecordData, RecordType};
+use super::{RecordData, RecordType, Rr};
 use crate::error::Error;
-use libresolv_sys::ns_msg as Message;
-use libresolv_sys::ns_rr as Rr;
 
-use std::mem;
 use std::net::Ipv4Addr;
 
 #[derive(Debug, Clone)]
@@ -16,15 +13,16 @@ impl RecordData for A {
         RecordType::A
     }
 
-    fn extract(_msg: &mut Message, rr: &Rr) -> Result<A, Error> {
-        if rr.type_ != Self::get_record_type() as u16 {
-            return Err(Error::WrongRRType);
-        }
+    fn extract(rr: &Rr) -> Result<A, Error> {
+        rr.check_type::<Self>()?;
+        let mut octets = [0; 4];
+        octets.copy_from_slice(rr.read_bytes(0, 4)?);
         Ok(A {
-            address: Ipv4Addr::from(unsafe {
-                let input: &[u8; 4] = mem::transmute(rr.rdata);
-                *input
-            }),
+            address: Ipv4Addr::from(octets),
         })
     }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.address.octets().to_vec())
+    }
 }
@@ -0,0 +1,44 @@
+use super::{RecordData, RecordType, Rr};
+use crate::error::Error;
+use crate::wire;
+
+#[derive(Debug, Clone)]
+pub struct DNAME {
+    pub target: String,
+}
+
+impl RecordData for DNAME {
+    fn get_record_type() -> RecordType {
+        RecordType::DNAME
+    }
+
+    fn extract(rr: &Rr) -> Result<DNAME, Error> {
+        rr.check_type::<Self>()?;
+        Ok(DNAME {
+            target: rr.read_name(0)?.0,
+        })
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        wire::canonical_name(&self.target)
+    }
+}
+
+impl DNAME {
+    /// The name that `name` is an alias for, by this record owned by `owner` (RFC 6672):
+    /// the part of `name` below `owner` followed by the target.  Returns `None` if `name`
+    /// is not below `owner`, or if the result would be too long.
+    pub fn synthesize(&self, owner: &str, name: &str) -> Option<String> {
+        let owner = wire::labels(owner.as_bytes()).ok()?;
+        let name = wire::labels(name.as_bytes()).ok()?;
+        if name.len() <= owner.len() || !wire::is_subdomain(&name, &owner) {
+            return None;
+        }
+        let mut labels = name[..name.len() - owner.len()].to_vec();
+        labels.extend(wire::labels(self.target.as_bytes()).ok()?);
+        if labels.iter().map(|l| l.len() + 1).sum::<usize>() + 1 > wire::MAX_NAME_LEN {
+            return None;
+        }
+        Some(wire::name_to_string(&labels))
+    }
+}
@@ -0,0 +1,140 @@
+use super::{RecordData, RecordType, Rr};
+use crate::error::Error;
+use crate::wire;
+use std::ops::Deref;
+
+/// A DNSSEC public key (RFC 4034)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DNSKEY {
+    pub flags: u16,
+    /// Always 3
+    pub protocol: u8,
+    pub algorithm: u8,
+    pub public_key: Vec<u8>,
+}
+
+impl DNSKEY {
+    /// Flag bits
+    pub const FLAG_ZONE: u16 = 0x0100;
+    pub const FLAG_REVOKE: u16 = 0x0080;
+    pub const FLAG_SEP: u16 = 0x0001;
+
+    /// Whether this is a zone key, which may sign the zone's records
+    pub fn is_zone_key(&self) -> bool {
+        self.flags & DNSKEY::FLAG_ZONE != 0
+    }
+
+    /// Whether this is a secure entry point, i.e. a key-signing key
+    pub fn is_secure_entry_point(&self) -> bool {
+        self.flags & DNSKEY::FLAG_SEP != 0
+    }
+
+    /// Whether the key has been revoked (RFC 5011)
+    pub fn is_revoked(&self) -> bool {
+        self.flags & DNSKEY::FLAG_REVOKE != 0
+    }
+
+    /// The record data in wire format
+    pub fn to_rdata(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(4 + self.public_key.len());
+        wire::push_u16(&mut out, self.flags);
+        out.push(self.protocol);
+        out.push(self.algorithm);
+        out.extend_from_slice(&self.public_key);
+        out
+    }
+
+    /// The key tag, as RFC 4034 appendix B computes it
+    pub fn key_tag(&self) -> u16 {
+        let rdata = self.to_rdata();
+        // RSA/MD5 keys are tagged with bits of the modulus.
+        if self.algorithm == 1 {
+            let len = rdata.len();
+            if len < 4 {
+                return 0;
+            }
+            return u16::from_be_bytes([rdata[len - 3], rdata[len - 2]]);
+        }
+        let mut tag: u32 = 0;
+        for (i, &b) in rdata.iter().enumerate() {
+            tag += if i & 1 == 0 {
+                u32::from(b) << 8
+            } else {
+                u32::from(b)
+            };
+        }
+        tag += (tag >> 16) & 0xffff;
+        (tag & 0xffff) as u16
+    }
+
+    /// The DS record for this key, with owner name `owner`, of the given digest type
+    #[cfg(feature = "dnssec")]
+    pub fn ds(&self, owner: &str, digest_type: u8) -> Result<super::DS, Error> {
+        let mut data = wire::canonical_name(owner)?;
+        data.extend_from_slice(&self.to_rdata());
+        Ok(super::DS {
+            key_tag: self.key_tag(),
+            algorithm: self.algorithm,
+            digest_type,
+            digest: crate::dnssec::digest(digest_type, &data)?,
+        })
+    }
+
+    fn parse(rr: &Rr) -> Result<DNSKEY, Error> {
+        Ok(DNSKEY {
+            flags: rr.read_u16(0)?,
+            protocol: rr.read_u8(2)?,
+            algorithm: rr.read_u8(3)?,
+            public_key: rr.rdata()[4..].to_vec(),
+        })
+    }
+}
+
+impl RecordData for DNSKEY {
+    fn get_record_type() -> RecordType {
+        RecordType::DNSKEY
+    }
+
+    fn extract(rr: &Rr) -> Result<DNSKEY, Error> {
+        rr.check_type::<Self>()?;
+        DNSKEY::parse(rr)
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.to_rdata())
+    }
+}
+
+/// A key the child zone wants published in the parent as DS (RFC 7344), in DNSKEY form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CDNSKEY(pub DNSKEY);
+
+impl Deref for CDNSKEY {
+    type Target = DNSKEY;
+
+    fn deref(&self) -> &DNSKEY {
+        &self.0
+    }
+}
+
+impl CDNSKEY {
+    /// Whether this asks for the DS records to be removed (RFC 8078)
+    pub fn is_delete(&self) -> bool {
+        self.flags == 0 && self.algorithm == 0 && self.public_key == [0]
+    }
+}
+
+impl RecordData for CDNSKEY {
+    fn get_record_type() -> RecordType {
+        RecordType::CDNSKEY
+    }
+
+    fn extract(rr: &Rr) -> Result<CDNSKEY, Error> {
+        rr.check_type::<Self>()?;
+        DNSKEY::parse(rr).map(CDNSKEY)
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.to_rdata())
+    }
+}
@@ -0,0 +1,94 @@
+use super::{RecordData, RecordType, Rr};
+use crate::error::Error;
+use crate::wire;
+use std::ops::Deref;
+
+/// A delegation signer (RFC 4034): the digest of a key of the child zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DS {
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub digest_type: u8,
+    pub digest: Vec<u8>,
+}
+
+impl DS {
+    /// Whether this is the DS record of `key`, whose owner name is `owner`.  False if the
+    /// digest type is not supported.
+    #[cfg(feature = "dnssec")]
+    pub fn matches(&self, owner: &str, key: &super::DNSKEY) -> bool {
+        self.key_tag == key.key_tag()
+            && self.algorithm == key.algorithm
+            && key
+                .ds(owner, self.digest_type)
+                .is_ok_and(|ds| ds.digest == self.digest)
+    }
+
+    /// The record data in wire format
+    pub fn to_rdata(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(4 + self.digest.len());
+        wire::push_u16(&mut out, self.key_tag);
+        out.push(self.algorithm);
+        out.push(self.digest_type);
+        out.extend_from_slice(&self.digest);
+        out
+    }
+
+    fn parse(rr: &Rr) -> Result<DS, Error> {
+        Ok(DS {
+            key_tag: rr.read_u16(0)?,
+            algorithm: rr.read_u8(2)?,
+            digest_type: rr.read_u8(3)?,
+            digest: rr.rdata()[4..].to_vec(),
+        })
+    }
+}
+
+impl RecordData for DS {
+    fn get_record_type() -> RecordType {
+        RecordType::DS
+    }
+
+    fn extract(rr: &Rr) -> Result<DS, Error> {
+        rr.check_type::<Self>()?;
+        DS::parse(rr)
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.to_rdata())
+    }
+}
+
+/// A DS record the child zone wants published in the parent (RFC 7344)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CDS(pub DS);
+
+impl Deref for CDS {
+    type Target = DS;
+
+    fn deref(&self) -> &DS {
+        &self.0
+    }
+}
+
+impl CDS {
+    /// Whether this asks for the DS records to be removed (RFC 8078)
+    pub fn is_delete(&self) -> bool {
+        self.key_tag == 0 && self.algorithm == 0 && self.digest_type == 0 && self.digest == [0]
+    }
+}
+
+impl RecordData for CDS {
+    fn get_record_type() -> RecordType {
+        RecordType::CDS
+    }
+
+    fn extract(rr: &Rr) -> Result<CDS, Error> {
+        rr.check_type::<Self>()?;
+        DS::parse(rr).map(CDS)
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.to_rdata())
+    }
+}
@@ -1,7 +1,6 @@
 use crate::error::Error;
-use libresolv_sys::ns_msg as Message;
-use libresolv_sys::ns_rr as Rr;
-use std::ffi::CStr;
+use crate::wire;
+use byteorder::{BigEndian, ByteOrder};
 
 mod class;
 pub use self::class::Class;
@@ -12,7 +11,195 @@ pub trait RecordData: Sized {
     fn get_record_type() -> RecordType;
 
     /// Convert from low level resource record.  For internal use.
-    fn extract(msg: &mut Message, rr: &Rr) -> Result<Self, Error>;
+    fn extract(rr: &Rr) -> Result<Self, Error>;
+
+    /// The record data in the canonical wire format of RFC 4034 section 6.2: domain
+    /// names uncompressed and in lower case.  For internal use.
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error>;
+}
+
+/// A resource record as found in a message, before its data is decoded
+#[derive(Debug, Clone)]
+pub struct Rr<'a> {
+    msg: &'a [u8],
+    pub name: String,
+    pub type_: u16,
+    pub rr_class: u16,
+    pub ttl: u32,
+    pub rdlength: u16,
+    rdata: usize,
+}
+
+impl<'a> Rr<'a> {
+    /// Parse the record starting at `offset` in `msg`.  Returns it, and the offset of
+    /// the next record.  Question entries have no TTL or data.
+    pub(crate) fn parse(
+        msg: &'a [u8],
+        offset: usize,
+        question: bool,
+    ) -> Result<(Rr<'a>, usize), Error> {
+        let (labels, mut pos) = wire::read_name(msg, offset)?;
+        let fixed_len = if question { 4 } else { 10 };
+        let fixed = msg.get(pos..pos + fixed_len).ok_or(Error::ParseError)?;
+        let mut rr = Rr {
+            msg,
+            // Owner names are formatted as dn_expand does, which gives the root as "".
+            name: if labels.is_empty() {
+                String::new()
+            } else {
+                wire::name_to_string(&labels)
+            },
+            type_: BigEndian::read_u16(&fixed[0..2]),
+            rr_class: BigEndian::read_u16(&fixed[2..4]),
+            ttl: 0,
+            rdlength: 0,
+            rdata: pos + fixed_len,
+        };
+        pos += fixed_len;
+        if !question {
+            rr.ttl = BigEndian::read_u32(&fixed[4..8]);
+            rr.rdlength = BigEndian::read_u16(&fixed[8..10]);
+            pos += rr.rdlength as usize;
+            if pos > msg.len() {
+                return Err(Error::ParseError);
+            }
+        }
+        Ok((rr, pos))
+    }
+
+    /// The record data
+    pub fn rdata(&self) -> &'a [u8] {
+        &self.msg[self.rdata..self.rdata + self.rdlength as usize]
+    }
+
+    /// Check that this record is of type `T`
+    pub fn check_type<T: RecordData>(&self) -> Result<(), Error> {
+        if self.type_ != T::get_record_type() as u16 {
+            return Err(Error::WrongRRType);
+        }
+        Ok(())
+    }
+
+    /// Read `len` bytes of the record data starting at `offset`
+    pub fn read_bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], Error> {
+        self.rdata()
+            .get(offset..offset + len)
+            .ok_or(Error::ParseError)
+    }
+
+    pub fn read_u8(&self, offset: usize) -> Result<u8, Error> {
+        Ok(self.read_bytes(offset, 1)?[0])
+    }
+
+    pub fn read_u16(&self, offset: usize) -> Result<u16, Error> {
+        Ok(BigEndian::read_u16(self.read_bytes(offset, 2)?))
+    }
+
+    pub fn read_u32(&self, offset: usize) -> Result<u32, Error> {
+        Ok(BigEndian::read_u32(self.read_bytes(offset, 4)?))
+    }
+
+    /// Read a (possibly compressed) domain name at `offset` in the record data.  Returns
+    /// the name and the number of bytes it occupies in the record data.
+    pub fn read_name(&self, offset: usize) -> Result<(String, usize), Error> {
+        if offset >= self.rdlength as usize {
+            return Err(Error::UncompressError);
+        }
+        let start = self.rdata + offset;
+        let (labels, end) = wire::read_name(self.msg, start)?;
+        if end > self.rdata + self.rdlength as usize {
+            return Err(Error::UncompressError);
+        }
+        Ok((wire::name_to_string(&labels), end - start))
+    }
+}
+
+/// The canonical data (see `RecordData::canonical_rdata()`) of a record of any type: the
+/// names in the data of the types RFC 4034 section 6.2 lists (as amended by RFC 6840
+/// section 5.1) in lower case and uncompressed.  Other types are kept as they are.
+#[cfg(feature = "dnssec")]
+pub(crate) fn canonical_rdata(rr: &Rr) -> Result<Vec<u8>, Error> {
+    const NS: u16 = RecordType::NS as u16;
+    const MD: u16 = RecordType::MD as u16;
+    const MF: u16 = RecordType::MF as u16;
+    const CNAME: u16 = RecordType::CNAME as u16;
+    const SOA: u16 = RecordType::SOA as u16;
+    const MB: u16 = RecordType::MB as u16;
+    const MG: u16 = RecordType::MG as u16;
+    const MR: u16 = RecordType::MR as u16;
+    const PTR: u16 = RecordType::PTR as u16;
+    const MINFO: u16 = RecordType::MINFO as u16;
+    const MX: u16 = RecordType::MX as u16;
+    const RP: u16 = RecordType::RP as u16;
+    const AFSDB: u16 = RecordType::AFSDB as u16;
+    const RT: u16 = RecordType::RT as u16;
+    const PX: u16 = RecordType::PX as u16;
+    const SRV: u16 = RecordType::SRV as u16;
+    const NAPTR: u16 = RecordType::NAPTR as u16;
+    const KX: u16 = RecordType::KX as u16;
+    const DNAME: u16 = RecordType::DNAME as u16;
+    const RRSIG: u16 = RecordType::RRSIG as u16;
+    const SIG: u16 = RecordType::SIG as u16;
+    const NXT: u16 = RecordType::NXT as u16;
+    const A6: u16 = RecordType::A6 as u16;
+    match rr.type_ {
+        NS => self::NS::extract(rr)?.canonical_rdata(),
+        CNAME => self::CNAME::extract(rr)?.canonical_rdata(),
+        SOA => self::SOA::extract(rr)?.canonical_rdata(),
+        PTR => self::PTR::extract(rr)?.canonical_rdata(),
+        MX => self::MX::extract(rr)?.canonical_rdata(),
+        SRV => self::SRV::extract(rr)?.canonical_rdata(),
+        NAPTR => self::NAPTR::extract(rr)?.canonical_rdata(),
+        DNAME => self::DNAME::extract(rr)?.canonical_rdata(),
+        RRSIG => self::RRSIG::extract(rr)?.canonical_rdata(),
+        MD | MF | MB | MG | MR => lowercase_names(rr, 0, 1),
+        MINFO | RP => lowercase_names(rr, 0, 2),
+        AFSDB | RT | KX => lowercase_names(rr, 2, 1),
+        PX => lowercase_names(rr, 2, 2),
+        // The signer's name, and the next name, are followed by more data.
+        SIG => lowercase_name_within(rr, 18),
+        NXT => lowercase_name_within(rr, 0),
+        // The prefix length, then the address bits below it, then the prefix's name
+        // unless the prefix is empty (RFC 2874 section 3.1)
+        A6 => {
+            let prefix_len = rr.read_u8(0)?;
+            if prefix_len > 128 {
+                return Err(Error::ParseError);
+            }
+            let suffix_len = (128 - usize::from(prefix_len)).div_ceil(8);
+            lowercase_names(rr, 1 + suffix_len, usize::from(prefix_len > 0))
+        }
+        // HINFO is listed too, but holds no names.
+        _ => Ok(rr.rdata().to_vec()),
+    }
+}
+
+// The data of a record of a type without its own struct: `fixed` bytes, then `names`
+// names, in lower case
+#[cfg(feature = "dnssec")]
+fn lowercase_names(rr: &Rr, fixed: usize, names: usize) -> Result<Vec<u8>, Error> {
+    let mut out = rr.read_bytes(0, fixed)?.to_vec();
+    let mut offset = fixed;
+    for _ in 0..names {
+        let (name, len) = rr.read_name(offset)?;
+        out.extend_from_slice(&wire::canonical_name(&name)?);
+        offset += len;
+    }
+    if offset != rr.rdata().len() {
+        return Err(Error::ParseError);
+    }
+    Ok(out)
+}
+
+// The data of a record of a type without its own struct: `fixed` bytes, then a name in
+// lower case, then the rest of the data as it is
+#[cfg(feature = "dnssec")]
+fn lowercase_name_within(rr: &Rr, fixed: usize) -> Result<Vec<u8>, Error> {
+    let mut out = rr.read_bytes(0, fixed)?.to_vec();
+    let (name, len) = rr.read_name(fixed)?;
+    out.extend_from_slice(&wire::canonical_name(&name)?);
+    out.extend_from_slice(&rr.rdata()[fixed + len..]);
+    Ok(out)
 }
 
 /// A DNS response record of a particular type
@@ -26,16 +213,12 @@ pub struct Record<T: RecordData> {
 
 impl<T: RecordData> Record<T> {
     /// For internal use.
-    pub fn extract(msg: &mut Message, rr: &Rr) -> Result<Record<T>, Error> {
+    pub fn extract(rr: &Rr) -> Result<Record<T>, Error> {
         Ok(Record {
-            name: unsafe {
-                CStr::from_ptr(rr.name.as_ptr())
-                    .to_string_lossy()
-                    .into_owned()
-            },
+            name: rr.name.clone(),
             class: Class::from_rr_class(rr.rr_class)?,
             ttl: rr.ttl,
-            data: <T as RecordData>::extract(msg, rr)?,
+            data: <T as RecordData>::extract(rr)?,
         })
     }
 }
@@ -150,6 +333,12 @@ pub enum RecordType {
     CDS = 59,
     /// Child DNSKEY - RFC 7344
     CDNSKEY = 60,
+    /// RFC 8976 - Message digest for DNS zone
+    ZONEMD = 63,
+    /// RFC 9460 - General-purpose service binding
+    SVCB = 64,
+    /// RFC 9460 - Service binding for HTTPS
+    HTTPS = 65,
     /// RFC 2930 - Transtion Key record
     TKEY = 249,
     /// RFC 2845, 3645, 4635, 6895 - Transaction Signature
@@ -178,22 +367,44 @@ pub enum RecordType {
 pub use self::a::A;
 pub use self::aaaa::AAAA;
 pub use self::cname::CNAME;
+pub use self::dname::DNAME;
+pub use self::dnskey::{CDNSKEY, DNSKEY};
+pub use self::ds::{CDS, DS};
 pub use self::mx::MX;
+pub use self::naptr::NAPTR;
 pub use self::ns::NS;
+#[cfg(feature = "dnssec")]
+pub(crate) use self::nsec::read_type_bitmap;
+pub use self::nsec::NSEC;
+pub use self::nsec3::{NSEC3, NSEC3PARAM};
 pub use self::ptr::PTR;
+pub use self::rrsig::RRSIG;
 pub use self::soa::SOA;
 pub use self::srv::SRV;
+#[cfg(feature = "dnssec")]
+pub(crate) use self::svcb::read_params;
+pub use self::svcb::{SvcParam, HTTPS, SVCB};
 pub use self::tlsa::TLSA;
 pub use self::txt::TXT;
+pub use self::zonemd::ZONEMD;
 
 // FIXME: Add the other record types
 mod a;
 mod aaaa;
 mod cname;
+mod dname;
+mod dnskey;
+mod ds;
 mod mx;
+mod naptr;
 mod ns;
+mod nsec;
+mod nsec3;
 mod ptr;
+mod rrsig;
 mod soa;
 mod srv;
+mod svcb;
 mod tlsa;
 mod txt;
+mod zonemd;
@@ -1,11 +1,6 @@
-use super::{RecordData, RecordType};
+use super::{RecordData, RecordType, Rr};
 use crate::error::Error;
-use byteorder::{BigEndian, ByteOrder};
-use libresolv_sys::ns_msg as Message;
-use libresolv_sys::ns_rr as Rr;
-use libresolv_sys::MAXDNAME;
-use std::ffi::CStr;
-use std::slice;
+use crate::wire;
 
 #[derive(Debug, Clone)]
 pub struct MX {
@@ -18,36 +13,18 @@ impl RecordData for MX {
         RecordType::MX
     }
 
-    fn extract(msg: &mut Message, rr: &Rr) -> Result<MX, Error> {
-        if rr.type_ != Self::get_record_type() as u16 {
-            return Err(Error::WrongRRType);
-        }
-
-        let mut buffer: [u8; MAXDNAME as usize] = [0; MAXDNAME as usize];
-
-        let size = unsafe {
-            ::libresolv_sys::ns_name_uncompress(
-                msg._msg,
-                msg._eom,
-                rr.rdata.offset(2),
-                buffer.as_mut_ptr() as *mut i8,
-                MAXDNAME as usize,
-            )
-        };
-        if size < 0 {
-            return Err(Error::UncompressError);
-        }
-
+    fn extract(rr: &Rr) -> Result<MX, Error> {
+        rr.check_type::<Self>()?;
         Ok(MX {
-            preference: unsafe {
-                let slice: &[u8] = slice::from_raw_parts(rr.rdata, 2);
-                BigEndian::read_i16(slice)
-            },
-            exchange: unsafe {
-                CStr::from_ptr(buffer.as_ptr() as *const i8)
-                    .to_string_lossy()
-                    .into_owned()
-            },
+            preference: rr.read_u16(0)? as i16,
+            exchange: rr.read_name(2)?.0,
         })
     }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        wire::push_u16(&mut out, self.preference as u16);
+        out.extend_from_slice(&wire::canonical_name(&self.exchange)?);
+        Ok(out)
+    }
 }
@@ -0,0 +1,86 @@
+use super::{RecordData, RecordType, Rr};
+use crate::error::Error;
+use crate::wire;
+use std::convert::TryFrom;
+
+/// Naming authority pointer (RFC 3403): one rule of a Dynamic Delegation Discovery
+/// System application, such as ENUM, S-NAPTR or U-NAPTR
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NAPTR {
+    /// Rules are tried in increasing order; rules of equal order are alternatives
+    pub order: u16,
+    /// Lower values are preferred among rules of equal order
+    pub preference: u16,
+    /// Letters saying what to do with the result, e.g. "S", "A" or "U" for a terminal rule,
+    /// or empty to look up NAPTR records at the result
+    pub flags: String,
+    /// The application's services, e.g. "E2U+sip" or "WEB:http:https"
+    pub services: String,
+    /// A substitution expression, `!ere!replacement!`, applied to the application's
+    /// string; empty if `replacement` is the result
+    pub regexp: String,
+    /// The next domain name to look up, or "." if `regexp` gives the result
+    pub replacement: String,
+}
+
+impl NAPTR {
+    /// Whether `flags` has `flag`, ignoring case
+    pub fn has_flag(&self, flag: char) -> bool {
+        self.flags.chars().any(|c| c.eq_ignore_ascii_case(&flag))
+    }
+
+    /// Whether the rule ends the lookup, rather than leading to more NAPTR records
+    pub fn is_terminal(&self) -> bool {
+        !self.flags.is_empty()
+    }
+
+    /// The record data in wire format
+    pub fn to_rdata(&self) -> Result<Vec<u8>, Error> {
+        self.rdata(wire::encode_name(self.replacement.as_bytes())?)
+    }
+
+    fn rdata(&self, replacement: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        wire::push_u16(&mut out, self.order);
+        wire::push_u16(&mut out, self.preference);
+        for s in &[&self.flags, &self.services, &self.regexp] {
+            let len = u8::try_from(s.len()).map_err(|_| Error::ParseError)?;
+            out.push(len);
+            out.extend_from_slice(s.as_bytes());
+        }
+        out.extend_from_slice(&replacement);
+        Ok(out)
+    }
+}
+
+impl RecordData for NAPTR {
+    fn get_record_type() -> RecordType {
+        RecordType::NAPTR
+    }
+
+    fn extract(rr: &Rr) -> Result<NAPTR, Error> {
+        rr.check_type::<Self>()?;
+        let mut offset = 4;
+        let mut string = || -> Result<String, Error> {
+            let len = rr.read_u8(offset)? as usize;
+            let bytes = rr.read_bytes(offset + 1, len)?;
+            offset += 1 + len;
+            Ok(String::from_utf8_lossy(bytes).into_owned())
+        };
+        let (flags, services, regexp) = (string()?, string()?, string()?);
+        // The replacement is never compressed.
+        let (labels, _) = wire::read_name(rr.rdata(), offset)?;
+        Ok(NAPTR {
+            order: rr.read_u16(0)?,
+            preference: rr.read_u16(2)?,
+            flags,
+            services,
+            regexp,
+            replacement: wire::name_to_string(&labels),
+        })
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        self.rdata(wire::canonical_name(&self.replacement)?)
+    }
+}
@@ -0,0 +1,88 @@
+use super::{RecordData, RecordType, Rr};
+use crate::error::Error;
+use crate::wire;
+
+/// Proof of the names and types that do not exist (RFC 4034): the next name in the zone,
+/// and the types the owner name has
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NSEC {
+    pub next_domain_name: String,
+    /// In ascending order
+    pub types: Vec<u16>,
+}
+
+impl NSEC {
+    /// Whether the owner name has records of type `rr_type`
+    pub fn has_type(&self, rr_type: u16) -> bool {
+        self.types.binary_search(&rr_type).is_ok()
+    }
+
+    /// The record data in wire format
+    pub fn to_rdata(&self) -> Result<Vec<u8>, Error> {
+        let mut out = wire::encode_name(self.next_domain_name.as_bytes())?;
+        out.extend_from_slice(&write_type_bitmap(&self.types));
+        Ok(out)
+    }
+}
+
+impl RecordData for NSEC {
+    fn get_record_type() -> RecordType {
+        RecordType::NSEC
+    }
+
+    fn extract(rr: &Rr) -> Result<NSEC, Error> {
+        rr.check_type::<Self>()?;
+        let (next_domain_name, len) = rr.read_name(0)?;
+        Ok(NSEC {
+            next_domain_name,
+            types: read_type_bitmap(&rr.rdata()[len..])?,
+        })
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        // RFC 6840 section 5.1: the next name keeps its case.
+        self.to_rdata()
+    }
+}
+
+/// Decode a type bitmap (RFC 4034 section 4.1.2) into the types it lists
+pub(crate) fn read_type_bitmap(data: &[u8]) -> Result<Vec<u16>, Error> {
+    let mut types = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (window, len) = match *rest {
+            [window, len, ..] if (1..=32).contains(&len) => (window, usize::from(len)),
+            _ => return Err(Error::ParseError),
+        };
+        let bitmap = rest.get(2..2 + len).ok_or(Error::ParseError)?;
+        for (i, &byte) in bitmap.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    types.push(u16::from(window) << 8 | (i * 8 + bit) as u16);
+                }
+            }
+        }
+        rest = &rest[2 + len..];
+    }
+    Ok(types)
+}
+
+/// Encode types as a type bitmap
+pub(crate) fn write_type_bitmap(types: &[u16]) -> Vec<u8> {
+    let mut types = types.to_vec();
+    types.sort_unstable();
+    types.dedup();
+    let mut out = Vec::new();
+    for window in types.chunk_by(|a, b| a >> 8 == b >> 8) {
+        let len = usize::from(window[window.len() - 1] & 0xff) / 8 + 1;
+        let mut bitmap = vec![0u8; len];
+        for &t in window {
+            let low = usize::from(t & 0xff);
+            bitmap[low / 8] |= 0x80 >> (low % 8);
+        }
+        out.push((window[0] >> 8) as u8);
+        out.push(len as u8);
+        out.extend_from_slice(&bitmap);
+    }
+    out
+}
@@ -0,0 +1,126 @@
+use super::nsec::{read_type_bitmap, write_type_bitmap};
+use super::{RecordData, RecordType, Rr};
+use crate::error::Error;
+
+/// Hashed proof of the names and types that do not exist (RFC 5155).  The owner name's
+/// first label is the base32hex hash of a name in the zone, and `next_hashed_owner` the
+/// next hash in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NSEC3 {
+    /// 1 for SHA-1
+    pub hash_algorithm: u8,
+    pub flags: u8,
+    /// Extra hash iterations
+    pub iterations: u16,
+    pub salt: Vec<u8>,
+    pub next_hashed_owner: Vec<u8>,
+    /// In ascending order
+    pub types: Vec<u16>,
+}
+
+impl NSEC3 {
+    /// The hash span may hold unsigned delegations
+    pub const FLAG_OPT_OUT: u8 = 0x01;
+
+    /// Whether the hash span may hold unsigned delegations
+    pub fn is_opt_out(&self) -> bool {
+        self.flags & NSEC3::FLAG_OPT_OUT != 0
+    }
+
+    /// Whether the owner name has records of type `rr_type`
+    pub fn has_type(&self, rr_type: u16) -> bool {
+        self.types.binary_search(&rr_type).is_ok()
+    }
+
+    /// The hash of `name` in this record's zone (RFC 5155 section 5)
+    #[cfg(feature = "dnssec")]
+    pub fn hash(&self, name: &str) -> Result<Vec<u8>, Error> {
+        crate::dnssec::nsec3_hash(self.hash_algorithm, name, &self.salt, self.iterations)
+    }
+
+    /// The record data in wire format
+    pub fn to_rdata(&self) -> Vec<u8> {
+        let mut out = vec![self.hash_algorithm, self.flags];
+        out.extend_from_slice(&self.iterations.to_be_bytes());
+        out.push(self.salt.len() as u8);
+        out.extend_from_slice(&self.salt);
+        out.push(self.next_hashed_owner.len() as u8);
+        out.extend_from_slice(&self.next_hashed_owner);
+        out.extend_from_slice(&write_type_bitmap(&self.types));
+        out
+    }
+}
+
+impl RecordData for NSEC3 {
+    fn get_record_type() -> RecordType {
+        RecordType::NSEC3
+    }
+
+    fn extract(rr: &Rr) -> Result<NSEC3, Error> {
+        rr.check_type::<Self>()?;
+        let salt_len = usize::from(rr.read_u8(4)?);
+        let salt = rr.read_bytes(5, salt_len)?.to_vec();
+        let hash_len = usize::from(rr.read_u8(5 + salt_len)?);
+        let next_hashed_owner = rr.read_bytes(6 + salt_len, hash_len)?.to_vec();
+        Ok(NSEC3 {
+            hash_algorithm: rr.read_u8(0)?,
+            flags: rr.read_u8(1)?,
+            iterations: rr.read_u16(2)?,
+            salt,
+            next_hashed_owner,
+            types: read_type_bitmap(&rr.rdata()[6 + salt_len + hash_len..])?,
+        })
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.to_rdata())
+    }
+}
+
+/// The NSEC3 parameters a zone is signed with, for authoritative servers (RFC 5155)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NSEC3PARAM {
+    pub hash_algorithm: u8,
+    /// Zero in zones; the opt-out flag is only meaningful in NSEC3 records
+    pub flags: u8,
+    pub iterations: u16,
+    pub salt: Vec<u8>,
+}
+
+impl NSEC3PARAM {
+    /// The hash of `name` with these parameters
+    #[cfg(feature = "dnssec")]
+    pub fn hash(&self, name: &str) -> Result<Vec<u8>, Error> {
+        crate::dnssec::nsec3_hash(self.hash_algorithm, name, &self.salt, self.iterations)
+    }
+
+    /// The record data in wire format
+    pub fn to_rdata(&self) -> Vec<u8> {
+        let mut out = vec![self.hash_algorithm, self.flags];
+        out.extend_from_slice(&self.iterations.to_be_bytes());
+        out.push(self.salt.len() as u8);
+        out.extend_from_slice(&self.salt);
+        out
+    }
+}
+
+impl RecordData for NSEC3PARAM {
+    fn get_record_type() -> RecordType {
+        RecordType::NSEC3PARAM
+    }
+
+    fn extract(rr: &Rr) -> Result<NSEC3PARAM, Error> {
+        rr.check_type::<Self>()?;
+        let salt_len = usize::from(rr.read_u8(4)?);
+        Ok(NSEC3PARAM {
+            hash_algorithm: rr.read_u8(0)?,
+            flags: rr.read_u8(1)?,
+            iterations: rr.read_u16(2)?,
+            salt: rr.read_bytes(5, salt_len)?.to_vec(),
+        })
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.to_rdata())
+    }
+}
@@ -1,10 +1,6 @@
-use super::{RecordData, RecordType};
+use super::{RecordData, RecordType, Rr};
 use crate::error::Error;
-use libresolv_sys::ns_msg as Message;
-use libresolv_sys::ns_rr as Rr;
-use libresolv_sys::MAXDNAME;
-
-use std::ffi::CStr;
+use crate::wire;
 
 #[derive(Debug, Clone)]
 pub struct PTR {
@@ -16,32 +12,14 @@ impl RecordData for PTR {
         RecordType::PTR
     }
 
-    fn extract(msg: &mut Message, rr: &Rr) -> Result<PTR, Error> {
-        if rr.type_ != Self::get_record_type() as u16 {
-            return Err(Error::WrongRRType);
-        }
-
-        let mut buffer: [u8; MAXDNAME as usize] = [0; MAXDNAME as usize];
-
-        let size = unsafe {
-            ::libresolv_sys::ns_name_uncompress(
-                msg._msg,
-                msg._eom,
-                rr.rdata,
-                buffer.as_mut_ptr() as *mut i8,
-                MAXDNAME as usize,
-            )
-        };
-        if size < 0 {
-            return Err(Error::UncompressError);
-        }
-
+    fn extract(rr: &Rr) -> Result<PTR, Error> {
+        rr.check_type::<Self>()?;
         Ok(PTR {
-            dname: unsafe {
-                CStr::from_ptr(buffer.as_ptr() as *const i8)
-                    .to_string_lossy()
-                    .into_owned()
-            },
+            dname: rr.read_name(0)?.0,
         })
     }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        wire::canonical_name(&self.dname)
+    }
 }
@@ -0,0 +1,160 @@
+use super::{Record, RecordData, RecordType, Rr};
+use crate::error::Error;
+use crate::wire;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A DNSSEC signature over an RRset (RFC 4034)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RRSIG {
+    /// Type of the records signed
+    pub type_covered: u16,
+    pub algorithm: u8,
+    /// Number of labels in the owner name of the records signed, not counting a leading
+    /// wildcard
+    pub labels: u8,
+    pub original_ttl: u32,
+    /// Seconds since the epoch, modulo 2^32
+    pub expiration: u32,
+    /// Seconds since the epoch, modulo 2^32
+    pub inception: u32,
+    /// Key tag of the signing DNSKEY
+    pub key_tag: u16,
+    /// Owner of the signing DNSKEY, i.e. the zone
+    pub signer_name: String,
+    pub signature: Vec<u8>,
+}
+
+impl RRSIG {
+    /// When the signature expires, taking the 32-bit time to be the one nearest to now
+    pub fn expiration_time(&self) -> SystemTime {
+        serial_time(self.expiration, SystemTime::now())
+    }
+
+    /// When the signature becomes valid, taking the 32-bit time to be the one nearest to
+    /// now
+    pub fn inception_time(&self) -> SystemTime {
+        serial_time(self.inception, SystemTime::now())
+    }
+
+    /// Whether the signature is valid now
+    pub fn is_current(&self) -> bool {
+        self.is_current_at(SystemTime::now())
+    }
+
+    /// Whether the signature is valid at `time`, comparing times in serial number
+    /// arithmetic as RFC 4034 section 3.1.5 requires
+    pub fn is_current_at(&self, time: SystemTime) -> bool {
+        serial_time(self.inception, time) <= time && time <= serial_time(self.expiration, time)
+    }
+
+    /// Time left until the signature expires, or `None` if it has
+    pub fn remaining(&self) -> Option<Duration> {
+        self.expiration_time()
+            .duration_since(SystemTime::now())
+            .ok()
+    }
+
+    // The record data up to the signature, with the signer name in canonical form
+    fn rdata_fields(&self) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        wire::push_u16(&mut out, self.type_covered);
+        out.push(self.algorithm);
+        out.push(self.labels);
+        wire::push_u32(&mut out, self.original_ttl);
+        wire::push_u32(&mut out, self.expiration);
+        wire::push_u32(&mut out, self.inception);
+        wire::push_u16(&mut out, self.key_tag);
+        out.extend_from_slice(&wire::canonical_name(&self.signer_name)?);
+        Ok(out)
+    }
+
+    /// The data this signature signs over `records` (RFC 4034 section 3.1.8.1): the
+    /// signature's own fields, then each record in canonical form and order, with the
+    /// original TTL and, for records synthesised from a wildcard, the wildcard owner name.
+    /// Duplicate records are included once.
+    pub fn signed_data<T: RecordData>(&self, records: &[Record<T>]) -> Result<Vec<u8>, Error> {
+        if T::get_record_type() as u16 != self.type_covered {
+            return Err(Error::WrongRRType);
+        }
+        let rrs = records
+            .iter()
+            .map(|r| Ok((r.name.clone(), r.class as u16, r.data.canonical_rdata()?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        self.signed_data_raw(rrs)
+    }
+
+    // As `signed_data()`, given the owner name, class and canonical data of each record
+    pub(crate) fn signed_data_raw(
+        &self,
+        records: Vec<(String, u16, Vec<u8>)>,
+    ) -> Result<Vec<u8>, Error> {
+        let mut rrs: Vec<(Vec<u8>, u16, Vec<u8>)> = Vec::with_capacity(records.len());
+        for (name, class, rdata) in records {
+            let owner = wire::labels(name.as_bytes())?;
+            let labels = self.labels as usize;
+            let owner = if owner.len() > labels {
+                let mut wildcard = vec![b"*".to_vec()];
+                wildcard.extend_from_slice(&owner[owner.len() - labels..]);
+                wildcard
+            } else {
+                owner
+            };
+            let mut name = Vec::new();
+            for label in &owner {
+                name.push(label.len() as u8);
+                name.extend(label.iter().map(u8::to_ascii_lowercase));
+            }
+            name.push(0);
+            rrs.push((name, class, rdata));
+        }
+        rrs.sort_by(|a, b| a.2.cmp(&b.2));
+        rrs.dedup();
+
+        let mut out = self.rdata_fields()?;
+        for (owner, class, rdata) in rrs {
+            out.extend_from_slice(&owner);
+            wire::push_u16(&mut out, self.type_covered);
+            wire::push_u16(&mut out, class);
+            wire::push_u32(&mut out, self.original_ttl);
+            wire::push_u16(&mut out, rdata.len() as u16);
+            out.extend_from_slice(&rdata);
+        }
+        Ok(out)
+    }
+}
+
+// The time whose seconds since the epoch, modulo 2^32, are `value`, nearest to `now`
+fn serial_time(value: u32, now: SystemTime) -> SystemTime {
+    let now_secs = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let offset = value.wrapping_sub(now_secs as u32) as i32;
+    let secs = (now_secs as i64 + i64::from(offset)).max(0) as u64;
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+impl RecordData for RRSIG {
+    fn get_record_type() -> RecordType {
+        RecordType::RRSIG
+    }
+
+    fn extract(rr: &Rr) -> Result<RRSIG, Error> {
+        rr.check_type::<Self>()?;
+        let (signer_name, len) = rr.read_name(18)?;
+        Ok(RRSIG {
+            type_covered: rr.read_u16(0)?,
+            algorithm: rr.read_u8(2)?,
+            labels: rr.read_u8(3)?,
+            original_ttl: rr.read_u32(4)?,
+            expiration: rr.read_u32(8)?,
+            inception: rr.read_u32(12)?,
+            key_tag: rr.read_u16(16)?,
+            signer_name,
+            signature: rr.rdata()[18 + len..].to_vec(),
+        })
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        let mut out = self.rdata_fields()?;
+        out.extend_from_slice(&self.signature);
+        Ok(out)
+    }
+}
@@ -1,11 +1,6 @@
-use super::{RecordData, RecordType};
+use super::{RecordData, RecordType, Rr};
 use crate::error::Error;
-use byteorder::{BigEndian, ByteOrder};
-use libresolv_sys::ns_msg as Message;
-use libresolv_sys::ns_rr as Rr;
-use libresolv_sys::MAXHOSTNAMELEN;
-use std::ffi::CStr;
-use std::slice;
+use crate::wire;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SRV {
@@ -20,43 +15,22 @@ impl RecordData for SRV {
         RecordType::SRV
     }
 
-    fn extract(msg: &mut Message, rr: &Rr) -> Result<SRV, Error> {
-        if rr.type_ != Self::get_record_type() as u16 {
-            return Err(Error::WrongRRType);
-        }
-
-        let mut buffer: [u8; MAXHOSTNAMELEN as usize] = [0; MAXHOSTNAMELEN as usize];
-        let size = unsafe {
-            ::libresolv_sys::ns_name_uncompress(
-                msg._msg,
-                msg._eom,
-                rr.rdata.offset(6),
-                buffer.as_mut_ptr() as *mut i8,
-                MAXHOSTNAMELEN as usize,
-            )
-        };
-        if size < 0 {
-            return Err(Error::UncompressError);
-        }
-
+    fn extract(rr: &Rr) -> Result<SRV, Error> {
+        rr.check_type::<Self>()?;
         Ok(SRV {
-            priority: unsafe {
-                let slice: &[u8] = slice::from_raw_parts(rr.rdata.offset(0), 2);
-                BigEndian::read_u16(slice)
-            },
-            weight: unsafe {
-                let slice: &[u8] = slice::from_raw_parts(rr.rdata.offset(2), 2);
-                BigEndian::read_u16(slice)
-            },
-            port: unsafe {
-                let slice: &[u8] = slice::from_raw_parts(rr.rdata.offset(4), 2);
-                BigEndian::read_u16(slice)
-            },
-            name: unsafe {
-                CStr::from_ptr(buffer.as_ptr() as *const i8)
-                    .to_string_lossy()
-                    .into_owned()
-            },
+            priority: rr.read_u16(0)?,
+            weight: rr.read_u16(2)?,
+            port: rr.read_u16(4)?,
+            name: rr.read_name(6)?.0,
         })
     }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        wire::push_u16(&mut out, self.priority);
+        wire::push_u16(&mut out, self.weight);
+        wire::push_u16(&mut out, self.port);
+        out.extend_from_slice(&wire::canonical_name(&self.name)?);
+        Ok(out)
+    }
 }
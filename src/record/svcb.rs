@@ -0,0 +1,355 @@
+use super::{RecordData, RecordType, Rr};
+use crate::error::Error;
+use crate::wire;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::ops::Deref;
+
+/// A service parameter of an SVCB or HTTPS record (RFC 9460 section 7)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SvcParam {
+    /// Keys a client must understand to use the record
+    Mandatory(Vec<u16>),
+    /// Application protocols (ALPN IDs) the service supports
+    Alpn(Vec<String>),
+    /// The scheme's default protocol is not supported; see `Alpn`
+    NoDefaultAlpn,
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// An ECHConfigList for TLS Encrypted Client Hello
+    Ech(Vec<u8>),
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// A key this crate does not know, and its value
+    Unknown(u16, Vec<u8>),
+}
+
+impl SvcParam {
+    pub const MANDATORY: u16 = 0;
+    pub const ALPN: u16 = 1;
+    pub const NO_DEFAULT_ALPN: u16 = 2;
+    pub const PORT: u16 = 3;
+    pub const IPV4HINT: u16 = 4;
+    pub const ECH: u16 = 5;
+    pub const IPV6HINT: u16 = 6;
+
+    pub fn key(&self) -> u16 {
+        match *self {
+            SvcParam::Mandatory(_) => SvcParam::MANDATORY,
+            SvcParam::Alpn(_) => SvcParam::ALPN,
+            SvcParam::NoDefaultAlpn => SvcParam::NO_DEFAULT_ALPN,
+            SvcParam::Port(_) => SvcParam::PORT,
+            SvcParam::Ipv4Hint(_) => SvcParam::IPV4HINT,
+            SvcParam::Ech(_) => SvcParam::ECH,
+            SvcParam::Ipv6Hint(_) => SvcParam::IPV6HINT,
+            SvcParam::Unknown(key, _) => key,
+        }
+    }
+
+    fn parse(key: u16, value: &[u8]) -> Result<SvcParam, Error> {
+        let param = match key {
+            SvcParam::MANDATORY if !value.is_empty() && value.len().is_multiple_of(2) => {
+                SvcParam::Mandatory(
+                    value
+                        .chunks(2)
+                        .map(|k| u16::from_be_bytes([k[0], k[1]]))
+                        .collect(),
+                )
+            }
+            SvcParam::ALPN if !value.is_empty() => {
+                let mut ids = Vec::new();
+                let mut rest = value;
+                while let Some((&len, tail)) = rest.split_first() {
+                    let len = usize::from(len);
+                    if len == 0 || tail.len() < len {
+                        return Err(Error::ParseError);
+                    }
+                    ids.push(String::from_utf8_lossy(&tail[..len]).into_owned());
+                    rest = &tail[len..];
+                }
+                SvcParam::Alpn(ids)
+            }
+            SvcParam::NO_DEFAULT_ALPN if value.is_empty() => SvcParam::NoDefaultAlpn,
+            SvcParam::PORT if value.len() == 2 => {
+                SvcParam::Port(u16::from_be_bytes([value[0], value[1]]))
+            }
+            SvcParam::IPV4HINT if !value.is_empty() && value.len().is_multiple_of(4) => {
+                SvcParam::Ipv4Hint(
+                    value
+                        .chunks(4)
+                        .map(|a| Ipv4Addr::new(a[0], a[1], a[2], a[3]))
+                        .collect(),
+                )
+            }
+            SvcParam::ECH if !value.is_empty() => SvcParam::Ech(value.to_vec()),
+            SvcParam::IPV6HINT if !value.is_empty() && value.len().is_multiple_of(16) => {
+                SvcParam::Ipv6Hint(
+                    value
+                        .chunks(16)
+                        .map(|a| {
+                            let mut octets = [0; 16];
+                            octets.copy_from_slice(a);
+                            Ipv6Addr::from(octets)
+                        })
+                        .collect(),
+                )
+            }
+            // The reserved "invalid key"
+            65535 => return Err(Error::ParseError),
+            key if key > SvcParam::IPV6HINT => SvcParam::Unknown(key, value.to_vec()),
+            _ => return Err(Error::ParseError),
+        };
+        Ok(param)
+    }
+
+    fn value(&self) -> Vec<u8> {
+        match *self {
+            SvcParam::Mandatory(ref keys) => keys.iter().flat_map(|k| k.to_be_bytes()).collect(),
+            SvcParam::Alpn(ref ids) => {
+                let mut out = Vec::new();
+                for id in ids {
+                    out.push(id.len() as u8);
+                    out.extend_from_slice(id.as_bytes());
+                }
+                out
+            }
+            SvcParam::NoDefaultAlpn => Vec::new(),
+            SvcParam::Port(port) => port.to_be_bytes().to_vec(),
+            SvcParam::Ipv4Hint(ref addrs) => addrs.iter().flat_map(|a| a.octets()).collect(),
+            SvcParam::Ech(ref config) | SvcParam::Unknown(_, ref config) => config.clone(),
+            SvcParam::Ipv6Hint(ref addrs) => addrs.iter().flat_map(|a| a.octets()).collect(),
+        }
+    }
+}
+
+fn key_name(key: u16) -> String {
+    match key {
+        SvcParam::MANDATORY => "mandatory".to_owned(),
+        SvcParam::ALPN => "alpn".to_owned(),
+        SvcParam::NO_DEFAULT_ALPN => "no-default-alpn".to_owned(),
+        SvcParam::PORT => "port".to_owned(),
+        SvcParam::IPV4HINT => "ipv4hint".to_owned(),
+        SvcParam::ECH => "ech".to_owned(),
+        SvcParam::IPV6HINT => "ipv6hint".to_owned(),
+        key => format!("key{}", key),
+    }
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The parameter in presentation format, e.g. `alpn=h3,h2` (RFC 9460 section 7)
+impl fmt::Display for SvcParam {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            SvcParam::NoDefaultAlpn => return f.write_str("no-default-alpn"),
+            SvcParam::Unknown(key, ref value) if value.is_empty() => {
+                return write!(f, "{}", key_name(key))
+            }
+            SvcParam::Mandatory(ref keys) => {
+                join(&keys.iter().map(|&k| key_name(k)).collect::<Vec<_>>())
+            }
+            SvcParam::Alpn(ref ids) => join(
+                &ids.iter()
+                    .map(|id| id.replace('\\', "\\\\").replace(',', "\\,"))
+                    .collect::<Vec<_>>(),
+            ),
+            SvcParam::Port(port) => port.to_string(),
+            SvcParam::Ipv4Hint(ref addrs) => join(addrs),
+            SvcParam::Ech(ref config) => wire::base64(config),
+            SvcParam::Ipv6Hint(ref addrs) => join(addrs),
+            SvcParam::Unknown(_, ref value) => {
+                let mut out = String::from("\"");
+                for &c in value {
+                    match c {
+                        b'"' | b'\\' => {
+                            out.push('\\');
+                            out.push(c as char);
+                        }
+                        0x20..=0x7e => out.push(c as char),
+                        _ => out.push_str(&format!("\\{:03}", c)),
+                    }
+                }
+                out.push('"');
+                out
+            }
+        };
+        write!(f, "{}={}", key_name(self.key()), value)
+    }
+}
+
+/// Parse the SvcParams at the end of SVCB record data, which must be in increasing order
+/// of key
+pub(crate) fn read_params(mut data: &[u8]) -> Result<Vec<SvcParam>, Error> {
+    let mut params: Vec<SvcParam> = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 4 {
+            return Err(Error::ParseError);
+        }
+        let key = u16::from_be_bytes([data[0], data[1]]);
+        let len = usize::from(u16::from_be_bytes([data[2], data[3]]));
+        let value = data.get(4..4 + len).ok_or(Error::ParseError)?;
+        if params.last().is_some_and(|p| p.key() >= key) {
+            return Err(Error::ParseError);
+        }
+        params.push(SvcParam::parse(key, value)?);
+        data = &data[4 + len..];
+    }
+    Ok(params)
+}
+
+/// Service binding (RFC 9460): where, and with which parameters, a service is offered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SVCB {
+    /// 0 in alias mode; otherwise lower values are preferred
+    pub priority: u16,
+    /// "." for the owner name in service mode, or for no service in alias mode
+    pub target: String,
+    /// In increasing order of key
+    pub params: Vec<SvcParam>,
+}
+
+impl SVCB {
+    /// Whether the record only says the service is offered under `target`'s name
+    pub fn is_alias(&self) -> bool {
+        self.priority == 0
+    }
+
+    pub fn param(&self, key: u16) -> Option<&SvcParam> {
+        self.params.iter().find(|p| p.key() == key)
+    }
+
+    /// The keys the client must understand, if any
+    pub fn mandatory(&self) -> &[u16] {
+        match self.param(SvcParam::MANDATORY) {
+            Some(SvcParam::Mandatory(keys)) => keys,
+            _ => &[],
+        }
+    }
+
+    /// The ALPN IDs, not counting the scheme's default
+    pub fn alpn(&self) -> &[String] {
+        match self.param(SvcParam::ALPN) {
+            Some(SvcParam::Alpn(ids)) => ids,
+            _ => &[],
+        }
+    }
+
+    pub fn no_default_alpn(&self) -> bool {
+        self.param(SvcParam::NO_DEFAULT_ALPN).is_some()
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        match self.param(SvcParam::PORT) {
+            Some(&SvcParam::Port(port)) => Some(port),
+            _ => None,
+        }
+    }
+
+    pub fn ipv4_hints(&self) -> &[Ipv4Addr] {
+        match self.param(SvcParam::IPV4HINT) {
+            Some(SvcParam::Ipv4Hint(addrs)) => addrs,
+            _ => &[],
+        }
+    }
+
+    pub fn ipv6_hints(&self) -> &[Ipv6Addr] {
+        match self.param(SvcParam::IPV6HINT) {
+            Some(SvcParam::Ipv6Hint(addrs)) => addrs,
+            _ => &[],
+        }
+    }
+
+    /// The ECHConfigList, if any
+    pub fn ech(&self) -> Option<&[u8]> {
+        match self.param(SvcParam::ECH) {
+            Some(SvcParam::Ech(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// The record data in wire format.  Parameters are written in order of key.
+    pub fn to_rdata(&self) -> Result<Vec<u8>, Error> {
+        let mut out = self.priority.to_be_bytes().to_vec();
+        out.extend_from_slice(&wire::encode_name(self.target.as_bytes())?);
+        let mut params: Vec<&SvcParam> = self.params.iter().collect();
+        params.sort_by_key(|p| p.key());
+        for param in params {
+            let value = param.value();
+            wire::push_u16(&mut out, param.key());
+            wire::push_u16(&mut out, value.len() as u16);
+            out.extend_from_slice(&value);
+        }
+        Ok(out)
+    }
+
+    fn parse(rr: &Rr) -> Result<SVCB, Error> {
+        let priority = rr.read_u16(0)?;
+        // The target name is never compressed.
+        let (labels, end) = wire::read_name(rr.rdata(), 2)?;
+        Ok(SVCB {
+            priority,
+            target: wire::name_to_string(&labels),
+            params: read_params(&rr.rdata()[end..])?,
+        })
+    }
+}
+
+impl AsRef<SVCB> for SVCB {
+    fn as_ref(&self) -> &SVCB {
+        self
+    }
+}
+
+impl RecordData for SVCB {
+    fn get_record_type() -> RecordType {
+        RecordType::SVCB
+    }
+
+    fn extract(rr: &Rr) -> Result<SVCB, Error> {
+        rr.check_type::<Self>()?;
+        SVCB::parse(rr)
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        self.to_rdata()
+    }
+}
+
+/// An SVCB record for HTTPS (RFC 9460 section 9), whose default protocol is
+/// "http/1.1" and default port 443
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HTTPS(pub SVCB);
+
+impl Deref for HTTPS {
+    type Target = SVCB;
+
+    fn deref(&self) -> &SVCB {
+        &self.0
+    }
+}
+
+impl AsRef<SVCB> for HTTPS {
+    fn as_ref(&self) -> &SVCB {
+        &self.0
+    }
+}
+
+impl RecordData for HTTPS {
+    fn get_record_type() -> RecordType {
+        RecordType::HTTPS
+    }
+
+    fn extract(rr: &Rr) -> Result<HTTPS, Error> {
+        rr.check_type::<Self>()?;
+        SVCB::parse(rr).map(HTTPS)
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        self.0.to_rdata()
+    }
+}
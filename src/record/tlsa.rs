@@ -1,10 +1,5 @@
-use super::{RecordData, RecordType};
+use super::{RecordData, RecordType, Rr};
 use crate::error::Error;
-use byteorder::ReadBytesExt;
-use libresolv_sys::ns_msg as Message;
-use libresolv_sys::ns_rr as Rr;
-use std::io::Cursor;
-use std::slice;
 
 #[derive(Debug, Clone)]
 pub struct TLSA {
@@ -19,19 +14,19 @@ impl RecordData for TLSA {
         RecordType::TLSA
     }
 
-    fn extract(_msg: &mut Message, rr: &Rr) -> Result<TLSA, Error> {
-        if rr.type_ != Self::get_record_type() as u16 {
-            return Err(Error::WrongRRType);
-        }
-
-        let mut reader =
-            unsafe { Cursor::new(slice::from_raw_parts(rr.rdata, rr.rdlength as usize)) };
-
+    fn extract(rr: &Rr) -> Result<TLSA, Error> {
+        rr.check_type::<Self>()?;
         Ok(TLSA {
-            usage: reader.read_u8().unwrap(),
-            selector: reader.read_u8().unwrap(),
-            matching_type: reader.read_u8().unwrap(),
-            data: reader.into_inner()[3..].to_vec(),
+            usage: rr.read_u8(0)?,
+            selector: rr.read_u8(1)?,
+            matching_type: rr.read_u8(2)?,
+            data: rr.rdata()[3..].to_vec(),
         })
     }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        let mut out = vec![self.usage, self.selector, self.matching_type];
+        out.extend_from_slice(&self.data);
+        Ok(out)
+    }
 }
@@ -1,7 +1,5 @@
-use super::{RecordData, RecordType};
+use super::{RecordData, RecordType, Rr};
 use crate::error::Error;
-use libresolv_sys::ns_msg as Message;
-use libresolv_sys::ns_rr as Rr;
 
 #[derive(Debug, Clone)]
 pub struct TXT {
@@ -13,16 +11,21 @@ impl RecordData for TXT {
         RecordType::TXT
     }
 
-    fn extract(_msg: &mut Message, rr: &Rr) -> Result<TXT, Error> {
-        if rr.type_ != Self::get_record_type() as u16 {
-            return Err(Error::WrongRRType);
-        }
-
-        let slice: &[u8] =
-            unsafe { ::std::slice::from_raw_parts(rr.rdata.offset(1), *rr.rdata as usize) };
+    fn extract(rr: &Rr) -> Result<TXT, Error> {
+        rr.check_type::<Self>()?;
+        let len = rr.read_u8(0)? as usize;
+        let slice: &[u8] = rr.read_bytes(1, len)?;
 
         Ok(TXT {
             dname: String::from_utf8_lossy(slice).into_owned(),
         })
     }
+
+    // Only the first string is kept, so this is the original data only if there was one
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        let bytes = self.dname.as_bytes();
+        let mut out = vec![bytes.len().min(255) as u8];
+        out.extend_from_slice(&bytes[..out[0] as usize]);
+        Ok(out)
+    }
 }
@@ -0,0 +1,45 @@
+use super::{RecordData, RecordType, Rr};
+use crate::error::Error;
+use crate::wire;
+
+/// A digest of the zone's contents, at its apex (RFC 8976)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZONEMD {
+    /// The SOA serial of the zone the digest is of
+    pub serial: u32,
+    pub scheme: u8,
+    pub hash_algorithm: u8,
+    pub digest: Vec<u8>,
+}
+
+impl ZONEMD {
+    /// The record data in wire format
+    pub fn to_rdata(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(6 + self.digest.len());
+        wire::push_u32(&mut out, self.serial);
+        out.push(self.scheme);
+        out.push(self.hash_algorithm);
+        out.extend_from_slice(&self.digest);
+        out
+    }
+}
+
+impl RecordData for ZONEMD {
+    fn get_record_type() -> RecordType {
+        RecordType::ZONEMD
+    }
+
+    fn extract(rr: &Rr) -> Result<ZONEMD, Error> {
+        rr.check_type::<Self>()?;
+        Ok(ZONEMD {
+            serial: rr.read_u32(0)?,
+            scheme: rr.read_u8(4)?,
+            hash_algorithm: rr.read_u8(5)?,
+            digest: rr.rdata()[6..].to_vec(),
+        })
+    }
+
+    fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.to_rdata())
+    }
+}
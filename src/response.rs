@@ -1,13 +1,196 @@
-use libresolv_sys::ns_msg as Message;
-use libresolv_sys::ns_rr as Rr;
-use libresolv_sys::ns_sect as NSSection;
-
 use crate::error::Error;
-use crate::record::{Record, RecordData};
+use crate::record::{Record, RecordData, RecordType, Rr, SOA};
+use crate::wire::{self, Header, HEADER_LEN};
+use byteorder::{BigEndian, ByteOrder};
+use std::fmt;
+
+/// The operation a message performs (RFC 1035, 1996, 2136, 8490)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Query,
+    /// Inverse query (obsolete)
+    IQuery,
+    Status,
+    Notify,
+    Update,
+    /// DNS Stateful Operations
+    DSO,
+    Unassigned(u16),
+}
+
+impl Opcode {
+    pub fn from_u16(value: u16) -> Opcode {
+        match value {
+            0 => Opcode::Query,
+            1 => Opcode::IQuery,
+            2 => Opcode::Status,
+            4 => Opcode::Notify,
+            5 => Opcode::Update,
+            6 => Opcode::DSO,
+            _ => Opcode::Unassigned(value),
+        }
+    }
+
+    pub fn to_u16(self) -> u16 {
+        match self {
+            Opcode::Query => 0,
+            Opcode::IQuery => 1,
+            Opcode::Status => 2,
+            Opcode::Notify => 4,
+            Opcode::Update => 5,
+            Opcode::DSO => 6,
+            Opcode::Unassigned(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Opcode::Query => write!(f, "QUERY"),
+            Opcode::IQuery => write!(f, "IQUERY"),
+            Opcode::Status => write!(f, "STATUS"),
+            Opcode::Notify => write!(f, "NOTIFY"),
+            Opcode::Update => write!(f, "UPDATE"),
+            Opcode::DSO => write!(f, "DSO"),
+            Opcode::Unassigned(value) => write!(f, "OPCODE{}", value),
+        }
+    }
+}
+
+/// A response code.  Values above 15 only occur as EDNS extended response codes, or in
+/// TSIG and TKEY records; see `Response::rcode()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rcode {
+    NoError,
+    FormErr,
+    ServFail,
+    NXDomain,
+    NotImp,
+    Refused,
+    YXDomain,
+    YXRRSet,
+    NXRRSet,
+    NotAuth,
+    NotZone,
+    DSOTypeNI,
+    /// Unsupported EDNS version (shares its value with TSIG's BADSIG)
+    BadVers,
+    BadKey,
+    BadTime,
+    BadMode,
+    BadName,
+    BadAlg,
+    BadTrunc,
+    BadCookie,
+    Unassigned(u16),
+}
+
+impl Rcode {
+    pub fn from_u16(value: u16) -> Rcode {
+        match value {
+            0 => Rcode::NoError,
+            1 => Rcode::FormErr,
+            2 => Rcode::ServFail,
+            3 => Rcode::NXDomain,
+            4 => Rcode::NotImp,
+            5 => Rcode::Refused,
+            6 => Rcode::YXDomain,
+            7 => Rcode::YXRRSet,
+            8 => Rcode::NXRRSet,
+            9 => Rcode::NotAuth,
+            10 => Rcode::NotZone,
+            11 => Rcode::DSOTypeNI,
+            16 => Rcode::BadVers,
+            17 => Rcode::BadKey,
+            18 => Rcode::BadTime,
+            19 => Rcode::BadMode,
+            20 => Rcode::BadName,
+            21 => Rcode::BadAlg,
+            22 => Rcode::BadTrunc,
+            23 => Rcode::BadCookie,
+            _ => Rcode::Unassigned(value),
+        }
+    }
 
+    pub fn to_u16(self) -> u16 {
+        match self {
+            Rcode::NoError => 0,
+            Rcode::FormErr => 1,
+            Rcode::ServFail => 2,
+            Rcode::NXDomain => 3,
+            Rcode::NotImp => 4,
+            Rcode::Refused => 5,
+            Rcode::YXDomain => 6,
+            Rcode::YXRRSet => 7,
+            Rcode::NXRRSet => 8,
+            Rcode::NotAuth => 9,
+            Rcode::NotZone => 10,
+            Rcode::DSOTypeNI => 11,
+            Rcode::BadVers => 16,
+            Rcode::BadKey => 17,
+            Rcode::BadTime => 18,
+            Rcode::BadMode => 19,
+            Rcode::BadName => 20,
+            Rcode::BadAlg => 21,
+            Rcode::BadTrunc => 22,
+            Rcode::BadCookie => 23,
+            Rcode::Unassigned(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for Rcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Rcode::NoError => "NOERROR",
+            Rcode::FormErr => "FORMERR",
+            Rcode::ServFail => "SERVFAIL",
+            Rcode::NXDomain => "NXDOMAIN",
+            Rcode::NotImp => "NOTIMP",
+            Rcode::Refused => "REFUSED",
+            Rcode::YXDomain => "YXDOMAIN",
+            Rcode::YXRRSet => "YXRRSET",
+            Rcode::NXRRSet => "NXRRSET",
+            Rcode::NotAuth => "NOTAUTH",
+            Rcode::NotZone => "NOTZONE",
+            Rcode::DSOTypeNI => "DSOTYPENI",
+            Rcode::BadVers => "BADVERS",
+            Rcode::BadKey => "BADKEY",
+            Rcode::BadTime => "BADTIME",
+            Rcode::BadMode => "BADMODE",
+            Rcode::BadName => "BADNAME",
+            Rcode::BadAlg => "BADALG",
+            Rcode::BadTrunc => "BADTRUNC",
+            Rcode::BadCookie => "BADCOOKIE",
+            Rcode::Unassigned(value) => return write!(f, "RCODE{}", value),
+        };
+        f.write_str(name)
+    }
+}
+
+const QR: u16 = 0x8000;
+const OPCODE: u16 = 0x7800;
+const AA: u16 = 0x0400;
+const TC: u16 = 0x0200;
+const RD: u16 = 0x0100;
+const RA: u16 = 0x0080;
+const Z: u16 = 0x0040;
+const AD: u16 = 0x0020;
+const CD: u16 = 0x0010;
+const RCODE: u16 = 0x000f;
+
+/// The second 16-bit word of the message header: flags, opcode and response code.
+/// `Display` lists the flags that are set the way dig does, e.g. `qr rd ra`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct Flags(pub u16);
 
 impl Flags {
+    /// No flags set, opcode QUERY and response code NOERROR
+    pub fn new() -> Flags {
+        Flags(0)
+    }
+
     #[inline]
     pub fn question_response(&self) -> bool {
         ((self.0 & 0x8000) >> 15) > 0
@@ -48,6 +231,84 @@ impl Flags {
     pub fn response_code(&self) -> u16 {
         self.0 & 0x000f
     }
+
+    #[inline]
+    pub fn opcode(&self) -> Opcode {
+        Opcode::from_u16(self.operation_code())
+    }
+    /// The response code from the header alone, without any EDNS extension
+    #[inline]
+    pub fn rcode(&self) -> Rcode {
+        Rcode::from_u16(self.response_code())
+    }
+
+    fn set(&mut self, bit: u16, value: bool) -> &mut Flags {
+        if value {
+            self.0 |= bit;
+        } else {
+            self.0 &= !bit;
+        }
+        self
+    }
+
+    pub fn set_question_response(&mut self, value: bool) -> &mut Flags {
+        self.set(QR, value)
+    }
+    /// Set the opcode.  Only its low four bits fit.
+    pub fn set_opcode(&mut self, opcode: Opcode) -> &mut Flags {
+        self.0 = (self.0 & !OPCODE) | ((opcode.to_u16() << 11) & OPCODE);
+        self
+    }
+    pub fn set_authoritative_answer(&mut self, value: bool) -> &mut Flags {
+        self.set(AA, value)
+    }
+    pub fn set_truncation_occurred(&mut self, value: bool) -> &mut Flags {
+        self.set(TC, value)
+    }
+    pub fn set_recursion_desired(&mut self, value: bool) -> &mut Flags {
+        self.set(RD, value)
+    }
+    pub fn set_recursion_available(&mut self, value: bool) -> &mut Flags {
+        self.set(RA, value)
+    }
+    pub fn set_authentic_data(&mut self, value: bool) -> &mut Flags {
+        self.set(AD, value)
+    }
+    pub fn set_checking_disabled(&mut self, value: bool) -> &mut Flags {
+        self.set(CD, value)
+    }
+    /// Set the response code.  Only its low four bits fit in the header; the rest go in
+    /// an OPT record.
+    pub fn set_rcode(&mut self, rcode: Rcode) -> &mut Flags {
+        self.0 = (self.0 & !RCODE) | (rcode.to_u16() & RCODE);
+        self
+    }
+}
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = [
+            (QR, "qr"),
+            (AA, "aa"),
+            (TC, "tc"),
+            (RD, "rd"),
+            (RA, "ra"),
+            (Z, "z"),
+            (AD, "ad"),
+            (CD, "cd"),
+        ];
+        let mut first = true;
+        for &(bit, name) in &names {
+            if self.0 & bit != 0 {
+                if !first {
+                    f.write_str(" ")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -58,115 +319,247 @@ pub enum Section {
     Additional,
 }
 impl Section {
-    fn ns_sect(&self) -> NSSection {
-        use ::libresolv_sys::{
-            __ns_sect_ns_s_an, __ns_sect_ns_s_ar, __ns_sect_ns_s_ns, __ns_sect_ns_s_qd,
-        };
-
+    fn index(&self) -> usize {
         match *self {
-            Section::Question => __ns_sect_ns_s_qd,
-            Section::Answer => __ns_sect_ns_s_an,
-            Section::Authority => __ns_sect_ns_s_ns,
-            Section::Additional => __ns_sect_ns_s_ar,
+            Section::Question => 0,
+            Section::Answer => 1,
+            Section::Authority => 2,
+            Section::Additional => 3,
         }
     }
 }
 
+/// What kind of answer a response is, following RFC 2308 and RFC 8020.  For negative
+/// answers, the SOA record from the authority section governs how long the answer may be
+/// cached; it is `None` if the server sent none.
+#[derive(Debug, Clone)]
+pub enum ResponseKind {
+    /// Records of the type asked for, at the name asked for or at the end of its aliases
+    Answer,
+    /// The name does not exist, nor does any name below it (RFC 8020)
+    NxDomain { soa: Option<Record<SOA>> },
+    /// The name exists but has no records of the type asked for
+    NoData { soa: Option<Record<SOA>> },
+    /// The server does not know the answer, but names the servers of a zone closer to it
+    Referral,
+    /// The name is an alias, but the name at the end of its aliases does not exist
+    /// (`nxdomain`) or has no records of the type asked for
+    CnameToNowhere {
+        nxdomain: bool,
+        soa: Option<Record<SOA>>,
+    },
+    /// The server failed with this response code (e.g. SERVFAIL or REFUSED), including
+    /// any extended bits from the OPT record
+    Failure(Rcode),
+}
+
 pub struct Response {
-    msg: Message,
-    #[allow(dead_code)] // buffer is where the real data is; keep it until Response drops.
-    buffer: Box<Vec<u8>>,
+    buffer: Vec<u8>,
+    header: Header,
+    // Offset of each record in the buffer, by section
+    offsets: [Vec<usize>; 4],
 }
 
 impl Response {
-    /// This is for internal use.
-    pub fn new(msg: Message, buffer: Box<Vec<u8>>) -> Response {
-        Response {
-            msg,
+    /// Parse a complete DNS message.  Every record is checked to lie within the message,
+    /// but record data is only decoded when records are retrieved.  Anything following
+    /// the last record is discarded.
+    pub fn from_buffer(mut buffer: Vec<u8>) -> Result<Response, Error> {
+        let header = Header::parse(&buffer)?;
+        let counts = [
+            header.qdcount,
+            header.ancount,
+            header.nscount,
+            header.arcount,
+        ];
+        let mut offsets: [Vec<usize>; 4] = Default::default();
+        let mut pos = HEADER_LEN;
+        for (section, count) in counts.iter().enumerate() {
+            for _ in 0..*count {
+                offsets[section].push(pos);
+                pos = Rr::parse(&buffer, pos, section == 0)?.1;
+            }
+        }
+        buffer.truncate(pos);
+        Ok(Response {
             buffer,
+            header,
+            offsets,
+        })
+    }
+
+    /// The complete message, as received.  This includes any records discarded by
+    /// `validate()`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Replace the TTL of every record (other than OPT pseudo-records) with `f(ttl)`
+    pub(crate) fn map_ttls<F: Fn(u32) -> u32>(&mut self, f: F) {
+        for offsets in &self.offsets[1..] {
+            for &offset in offsets {
+                // Already checked by from_buffer()
+                let (_, end) = wire::read_name(&self.buffer, offset).unwrap();
+                if BigEndian::read_u16(&self.buffer[end..end + 2]) == RecordType::OPT as u16 {
+                    continue;
+                }
+                let ttl = &mut self.buffer[end + 4..end + 8];
+                let mapped = f(BigEndian::read_u32(ttl));
+                BigEndian::write_u32(ttl, mapped);
+            }
+        }
+    }
+
+    /// Remove the records of a section for which `keep` is false.  The message itself is
+    /// unchanged, so other records' names still decompress.
+    pub(crate) fn retain(&mut self, section: Section, keep: &[bool]) {
+        let offsets = &mut self.offsets[section.index()];
+        let mut index = 0;
+        offsets.retain(|_| {
+            index += 1;
+            keep.get(index - 1).cloned().unwrap_or(true)
+        });
+    }
+
+    /// Classify the response by its response code, the question, and the records in it.
+    /// See `ResponseKind`.
+    pub fn kind(&self) -> Result<ResponseKind, Error> {
+        let rcode = self.rcode();
+        if rcode != Rcode::NoError && rcode != Rcode::NXDomain {
+            return Ok(ResponseKind::Failure(rcode));
+        }
+
+        let (qname, qtype, _) = wire::first_question(&self.buffer)?;
+        let names = self.chain_names(qname)?;
+        let in_chain = |owner: &[Vec<u8>]| names.iter().any(|n| wire::names_equal(n, owner));
+        let answered = self.owners(Section::Answer)?.iter().any(|(rr, owner)| {
+            (rr.type_ == qtype || qtype == RecordType::ANY as u16) && in_chain(owner)
+        });
+        if answered {
+            return Ok(ResponseKind::Answer);
         }
+
+        // The SOA of the zone holding the last name, or failing that, any SOA
+        let end = names.last().unwrap();
+        let authority = self.owners(Section::Authority)?;
+        let soa_index = authority
+            .iter()
+            .position(|(rr, owner)| {
+                rr.type_ == RecordType::SOA as u16 && wire::is_subdomain(end, owner)
+            })
+            .or_else(|| {
+                authority
+                    .iter()
+                    .position(|(rr, _)| rr.type_ == RecordType::SOA as u16)
+            });
+        let soa = match soa_index {
+            Some(i) => Some(self.get_record::<SOA>(Section::Authority, i)?),
+            None => None,
+        };
+        let has_ns = authority
+            .iter()
+            .any(|(rr, _)| rr.type_ == RecordType::NS as u16);
+
+        let nxdomain = rcode == Rcode::NXDomain;
+        Ok(
+            if !nxdomain && soa.is_none() && has_ns && !self.get_flags().authoritative_answer() {
+                ResponseKind::Referral
+            } else if names.len() > 1 {
+                ResponseKind::CnameToNowhere { nxdomain, soa }
+            } else if nxdomain {
+                ResponseKind::NxDomain { soa }
+            } else {
+                ResponseKind::NoData { soa }
+            },
+        )
     }
 
     /// Gets the ID field of the Name Server response
     pub fn get_id(&self) -> u16 {
-        self.msg._id
+        self.header.id
     }
 
     /// Gets flags (and opcodes) in the header of the Name Server response
     pub fn get_flags(&self) -> Flags {
-        Flags(self.msg._flags)
+        Flags(self.header.flags)
+    }
+
+    /// The response code, including the upper eight bits from the OPT record if there
+    /// is one (RFC 6891)
+    pub fn rcode(&self) -> Rcode {
+        let mut rcode = self.header.flags & RCODE;
+        for &offset in &self.offsets[Section::Additional.index()] {
+            if let Ok((rr, _)) = Rr::parse(&self.buffer, offset, false) {
+                if rr.type_ == RecordType::OPT as u16 {
+                    rcode |= ((rr.ttl >> 24) as u16) << 4;
+                    break;
+                }
+            }
+        }
+        Rcode::from_u16(rcode)
     }
 
     /// Returns a count of how many records exist in the given section
     pub fn get_section_count(&self, section: Section) -> usize {
-        self.msg._counts[section.ns_sect() as usize] as usize
+        self.offsets[section.index()].len()
+    }
+
+    /// Gets the undecoded record at an index in a section.  Returns an error if index is
+    /// out of bounds (use get_section_count()).
+    pub fn get_rr(&self, section: Section, index: usize) -> Result<Rr<'_>, Error> {
+        let offset = match self.offsets[section.index()].get(index) {
+            Some(o) => *o,
+            None => return Err(Error::NoSuchSectionIndex(section, index)),
+        };
+        Ok(Rr::parse(&self.buffer, offset, section == Section::Question)?.0)
     }
 
     /// Gets a record from a section.  Returns an error if index is out of bounds
     /// (use get_section_count()).  Also returns an error (at run-time) if assigned into
     /// a Record of the wrong type.
-    pub fn get_record<T>(&mut self, section: Section, index: usize) -> Result<Record<T>, Error>
+    pub fn get_record<T>(&self, section: Section, index: usize) -> Result<Record<T>, Error>
     where
         T: RecordData,
     {
-        if index >= self.get_section_count(section) {
-            return Err(Error::NoSuchSectionIndex(section, index));
-        }
-        let mut rr: Rr = Rr::default();
-        unsafe {
-            if ::libresolv_sys::ns_parserr(&mut self.msg, section.ns_sect(), index as i32, &mut rr)
-                < 0
-            {
-                return Err(Error::ParseError);
-            }
-        }
+        Record::extract(&self.get_rr(section, index)?)
+    }
 
-        Record::extract(&mut self.msg, &rr)
+    pub fn questions<T>(&self) -> RecordItems<'_, T>
+    where
+        T: RecordData,
+    {
+        self.records(Section::Question)
     }
 
-    pub fn questions<T>(&mut self) -> RecordItems<T>
+    pub fn answers<T>(&self) -> RecordItems<'_, T>
     where
         T: RecordData,
     {
-        RecordItems {
-            msg: &mut self.msg,
-            section: Section::Question,
-            index: 0,
-            _marker: ::std::marker::PhantomData,
-        }
+        self.records(Section::Answer)
     }
 
-    pub fn answers<T>(&mut self) -> RecordItems<T>
+    pub fn authorities<T>(&self) -> RecordItems<'_, T>
     where
         T: RecordData,
     {
-        RecordItems {
-            msg: &mut self.msg,
-            section: Section::Answer,
-            index: 0,
-            _marker: ::std::marker::PhantomData,
-        }
+        self.records(Section::Authority)
     }
 
-    pub fn authorities<T>(&mut self) -> RecordItems<T>
+    pub fn additional_records<T>(&self) -> RecordItems<'_, T>
     where
         T: RecordData,
     {
-        RecordItems {
-            msg: &mut self.msg,
-            section: Section::Authority,
-            index: 0,
-            _marker: ::std::marker::PhantomData,
-        }
+        self.records(Section::Additional)
     }
 
-    pub fn additional_records<T>(&mut self) -> RecordItems<T>
+    /// Iterate through the records of type `T` in a section
+    pub fn records<T>(&self, section: Section) -> RecordItems<'_, T>
     where
         T: RecordData,
     {
         RecordItems {
-            msg: &mut self.msg,
-            section: Section::Additional,
+            response: self,
+            section,
             index: 0,
             _marker: ::std::marker::PhantomData,
         }
@@ -175,7 +568,7 @@ impl Response {
 
 /// An iterator to iterate through DNS records
 pub struct RecordItems<'a, T: RecordData> {
-    msg: &'a mut Message,
+    response: &'a Response,
     section: Section,
     index: usize,
     _marker: ::std::marker::PhantomData<T>,
@@ -185,29 +578,16 @@ impl<'a, T: RecordData> Iterator for RecordItems<'a, T> {
     type Item = Record<T>;
 
     fn next(&mut self) -> Option<Record<T>> {
-        let len = self.msg._counts[self.section.ns_sect() as usize] as usize;
+        let len = self.response.get_section_count(self.section);
 
         loop {
             if self.index >= len {
                 return None;
             }
-
-            let mut rr: Rr = Rr::default();
-            unsafe {
-                if ::libresolv_sys::ns_parserr(
-                    self.msg,
-                    self.section.ns_sect(),
-                    self.index as i32,
-                    &mut rr,
-                ) < 0
-                {
-                    self.index = len;
-                    return None;
-                }
-            }
+            let index = self.index;
             self.index += 1;
 
-            match Record::extract(self.msg, &rr) {
+            match self.response.get_record(self.section, index) {
                 Ok(record) => return Some(record),
                 Err(_e) => {} // skip the record by looping around
             }
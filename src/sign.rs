@@ -0,0 +1,957 @@
+//! Offline zone signing (RFC 4035 section 2): add DNSKEY, RRSIG and NSEC or NSEC3
+//! records to the records of a zone, and write it out in zone file format.  Requires
+//! the `dnssec` feature.
+//!
+//! Keys are read from PKCS#8 documents, or from the `.key` and `.private` files BIND's
+//! `dnssec-keygen` writes.  Keys with the secure entry point flag sign the DNSKEY RRset
+//! and the others everything else; if all keys are of one kind, they sign everything.
+//!
+//! There is no zone file parser: records are given as `ZoneRecord`s, made from
+//! `Record`s or from the output of one.  Names below a delegation or DNAME (glue, or
+//! occluded records) are kept but neither signed nor covered by the denial chain.
+
+use crate::dnssec::{self, TrustAnchors, NSEC3_SHA1};
+use crate::dnssec::{ECDSAP256SHA256, ECDSAP384SHA384, ED25519, RSASHA256};
+use crate::error::Error;
+use crate::record::{self, Class, Record, RecordData, RecordType, Rr};
+use crate::record::{DNSKEY, NSEC, NSEC3, NSEC3PARAM, RRSIG, SOA};
+use crate::wire;
+use ring::rand::SystemRandom;
+use ring::rsa::{KeyPairComponents, PublicKeyComponents};
+use ring::signature::RsaKeyPair;
+use ring::signature::{self, EcdsaKeyPair, EcdsaSigningAlgorithm, Ed25519KeyPair, KeyPair};
+use std::collections::HashSet;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const NS: u16 = RecordType::NS as u16;
+const SOA_TYPE: u16 = RecordType::SOA as u16;
+const DNAME: u16 = RecordType::DNAME as u16;
+const DS: u16 = RecordType::DS as u16;
+const RRSIG_TYPE: u16 = RecordType::RRSIG as u16;
+const NSEC_TYPE: u16 = RecordType::NSEC as u16;
+const DNSKEY_TYPE: u16 = RecordType::DNSKEY as u16;
+const NSEC3_TYPE: u16 = RecordType::NSEC3 as u16;
+const NSEC3PARAM_TYPE: u16 = RecordType::NSEC3PARAM as u16;
+
+/// A record of a zone, of any type, with its data in canonical wire format
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneRecord {
+    pub name: String,
+    pub class: Class,
+    pub ttl: u32,
+    pub rr_type: u16,
+    pub rdata: Vec<u8>,
+}
+
+impl ZoneRecord {
+    pub fn new<T: RecordData>(record: &Record<T>) -> Result<ZoneRecord, Error> {
+        Ok(ZoneRecord {
+            name: record.name.clone(),
+            class: record.class,
+            ttl: record.ttl,
+            rr_type: T::get_record_type() as u16,
+            rdata: record.data.canonical_rdata()?,
+        })
+    }
+
+    /// The record decoded as type `T`
+    pub fn to_record<T: RecordData>(&self) -> Result<Record<T>, Error> {
+        let msg = self.to_wire()?;
+        let (rr, _) = Rr::parse(&msg, 0, false)?;
+        Record::extract(&rr)
+    }
+
+    // The data with the names in it in lower case, as signatures and digests cover it,
+    // whether or not it was given so
+    pub(crate) fn canonical_rdata(&self) -> Result<Vec<u8>, Error> {
+        let msg = self.to_wire()?;
+        let (rr, _) = Rr::parse(&msg, 0, false)?;
+        record::canonical_rdata(&rr)
+    }
+
+    fn to_wire(&self) -> Result<Vec<u8>, Error> {
+        let len = u16::try_from(self.rdata.len()).map_err(|_| Error::ParseError)?;
+        let mut msg = wire::encode_name(self.name.as_bytes())?;
+        wire::push_u16(&mut msg, self.rr_type);
+        wire::push_u16(&mut msg, self.class as u16);
+        wire::push_u32(&mut msg, self.ttl);
+        wire::push_u16(&mut msg, len);
+        msg.extend_from_slice(&self.rdata);
+        Ok(msg)
+    }
+}
+
+/// The record in zone file format, with a fully qualified owner name.  Types this crate
+/// does not know are written in the generic format of RFC 3597.
+impl fmt::Display for ZoneRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let class = match self.class {
+            Class::IN => "IN".to_owned(),
+            Class::CHAOS => "CH".to_owned(),
+            Class::HS => "HS".to_owned(),
+            class => format!("CLASS{}", class as u16),
+        };
+        write!(
+            f,
+            "{} {} {} {} ",
+            fqdn(&self.name),
+            self.ttl,
+            class,
+            type_name(self.rr_type)
+        )?;
+        match rdata_text(self.rr_type, &self.rdata) {
+            Some(text) => f.write_str(&text),
+            None if self.rdata.is_empty() => f.write_str("\\# 0"),
+            None => write!(f, "\\# {} {}", self.rdata.len(), hex(&self.rdata)),
+        }
+    }
+}
+
+/// Write `records` in zone file format, one per line
+pub fn write_zone<W: Write>(mut out: W, records: &[ZoneRecord]) -> Result<(), Error> {
+    for record in records {
+        writeln!(out, "{}", record)?;
+    }
+    Ok(())
+}
+
+fn fqdn(name: &str) -> String {
+    if name.ends_with('.') {
+        name.to_owned()
+    } else {
+        format!("{}.", name)
+    }
+}
+
+fn type_name(rr_type: u16) -> String {
+    const NAMES: [(RecordType, &str); 26] = [
+        (RecordType::A, "A"),
+        (RecordType::NS, "NS"),
+        (RecordType::CNAME, "CNAME"),
+        (RecordType::SOA, "SOA"),
+        (RecordType::PTR, "PTR"),
+        (RecordType::HINFO, "HINFO"),
+        (RecordType::MX, "MX"),
+        (RecordType::TXT, "TXT"),
+        (RecordType::AAAA, "AAAA"),
+        (RecordType::SRV, "SRV"),
+        (RecordType::NAPTR, "NAPTR"),
+        (RecordType::DNAME, "DNAME"),
+        (RecordType::DS, "DS"),
+        (RecordType::SSHFP, "SSHFP"),
+        (RecordType::RRSIG, "RRSIG"),
+        (RecordType::NSEC, "NSEC"),
+        (RecordType::DNSKEY, "DNSKEY"),
+        (RecordType::NSEC3, "NSEC3"),
+        (RecordType::NSEC3PARAM, "NSEC3PARAM"),
+        (RecordType::TLSA, "TLSA"),
+        (RecordType::CDS, "CDS"),
+        (RecordType::CDNSKEY, "CDNSKEY"),
+        (RecordType::ZONEMD, "ZONEMD"),
+        (RecordType::SVCB, "SVCB"),
+        (RecordType::HTTPS, "HTTPS"),
+        (RecordType::CAA, "CAA"),
+    ];
+    NAMES
+        .iter()
+        .find(|(t, _)| *t as u16 == rr_type)
+        .map_or_else(
+            || format!("TYPE{}", rr_type),
+            |(_, name)| (*name).to_owned(),
+        )
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+// A character string, quoted and escaped
+fn quoted(data: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &c in data {
+        match c {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(c as char);
+            }
+            0x20..=0x7e => out.push(c as char),
+            _ => out.push_str(&format!("\\{:03}", c)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// An RRSIG time as YYYYMMDDHHmmSS (RFC 4034 section 3.2)
+fn timestamp(secs: u32) -> String {
+    let (days, secs) = (i64::from(secs) / 86400, secs % 86400);
+    // Days to the civil date, after Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let (era, doe) = (z / 146_097, z % 146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + yoe + i64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+// Reads the fields of record data in order
+struct Fields<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn name(&mut self) -> Option<String> {
+        let (labels, end) = wire::read_name(self.data, self.pos).ok()?;
+        self.pos = end;
+        Some(fqdn(&wire::name_to_string(&labels)))
+    }
+
+    // A length-prefixed field
+    fn counted(&mut self) -> Option<&'a [u8]> {
+        let len = self.u8()?;
+        self.bytes(len.into())
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        self.pos = self.data.len();
+        rest
+    }
+
+    fn types(&mut self) -> Option<String> {
+        let types = record::read_type_bitmap(self.rest()).ok()?;
+        Some(
+            types
+                .into_iter()
+                .map(type_name)
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+}
+
+// The record data in presentation format, for the types this crate knows
+fn rdata_text(rr_type: u16, rdata: &[u8]) -> Option<String> {
+    const A: u16 = RecordType::A as u16;
+    const CNAME: u16 = RecordType::CNAME as u16;
+    const PTR: u16 = RecordType::PTR as u16;
+    const MX: u16 = RecordType::MX as u16;
+    const TXT: u16 = RecordType::TXT as u16;
+    const AAAA: u16 = RecordType::AAAA as u16;
+    const SRV: u16 = RecordType::SRV as u16;
+    const NAPTR: u16 = RecordType::NAPTR as u16;
+    const TLSA: u16 = RecordType::TLSA as u16;
+    const CDS: u16 = RecordType::CDS as u16;
+    const CDNSKEY: u16 = RecordType::CDNSKEY as u16;
+    const ZONEMD: u16 = RecordType::ZONEMD as u16;
+    const SVCB: u16 = RecordType::SVCB as u16;
+    const HTTPS: u16 = RecordType::HTTPS as u16;
+
+    let mut r = Fields {
+        data: rdata,
+        pos: 0,
+    };
+    let salt = |salt: &[u8]| {
+        if salt.is_empty() {
+            "-".to_owned()
+        } else {
+            hex(salt)
+        }
+    };
+    let text = match rr_type {
+        A => Ipv4Addr::from(<[u8; 4]>::try_from(r.bytes(4)?).ok()?).to_string(),
+        AAAA => Ipv6Addr::from(<[u8; 16]>::try_from(r.bytes(16)?).ok()?).to_string(),
+        NS | CNAME | DNAME | PTR => r.name()?,
+        MX => format!("{} {}", r.u16()?, r.name()?),
+        SOA_TYPE => format!(
+            "{} {} {} {} {} {} {}",
+            r.name()?,
+            r.name()?,
+            r.u32()?,
+            r.u32()?,
+            r.u32()?,
+            r.u32()?,
+            r.u32()?
+        ),
+        SRV => format!("{} {} {} {}", r.u16()?, r.u16()?, r.u16()?, r.name()?),
+        NAPTR => format!(
+            "{} {} {} {} {} {}",
+            r.u16()?,
+            r.u16()?,
+            quoted(r.counted()?),
+            quoted(r.counted()?),
+            quoted(r.counted()?),
+            r.name()?
+        ),
+        TXT if !rdata.is_empty() => {
+            let mut strings = Vec::new();
+            while r.pos < rdata.len() {
+                strings.push(quoted(r.counted()?));
+            }
+            strings.join(" ")
+        }
+        DS | CDS => format!("{} {} {} {}", r.u16()?, r.u8()?, r.u8()?, hex(r.rest())),
+        TLSA => format!("{} {} {} {}", r.u8()?, r.u8()?, r.u8()?, hex(r.rest())),
+        ZONEMD => format!("{} {} {} {}", r.u32()?, r.u8()?, r.u8()?, hex(r.rest())),
+        SVCB | HTTPS => {
+            let mut fields = vec![r.u16()?.to_string(), r.name()?];
+            let params = record::read_params(r.rest()).ok()?;
+            fields.extend(params.iter().map(ToString::to_string));
+            fields.join(" ")
+        }
+        DNSKEY_TYPE | CDNSKEY => format!(
+            "{} {} {} {}",
+            r.u16()?,
+            r.u8()?,
+            r.u8()?,
+            wire::base64(r.rest())
+        ),
+        RRSIG_TYPE => format!(
+            "{} {} {} {} {} {} {} {} {}",
+            type_name(r.u16()?),
+            r.u8()?,
+            r.u8()?,
+            r.u32()?,
+            timestamp(r.u32()?),
+            timestamp(r.u32()?),
+            r.u16()?,
+            r.name()?,
+            wire::base64(r.rest())
+        ),
+        NSEC_TYPE => format!("{} {}", r.name()?, r.types()?),
+        NSEC3_TYPE => format!(
+            "{} {} {} {} {} {}",
+            r.u8()?,
+            r.u8()?,
+            r.u16()?,
+            salt(r.counted()?),
+            dnssec::base32hex(r.counted()?).to_ascii_uppercase(),
+            r.types()?
+        ),
+        NSEC3PARAM_TYPE => format!(
+            "{} {} {} {}",
+            r.u8()?,
+            r.u8()?,
+            r.u16()?,
+            salt(r.counted()?)
+        ),
+        _ => return None,
+    };
+    if r.pos != rdata.len() {
+        return None;
+    }
+    Some(text.trim_end().to_owned())
+}
+
+#[derive(Debug)]
+enum Pair {
+    Rsa(RsaKeyPair),
+    Ecdsa(EcdsaKeyPair),
+    Ed25519(Ed25519KeyPair),
+}
+
+/// A private key, with the DNSKEY record of its public key
+#[derive(Debug)]
+pub struct SigningKey {
+    dnskey: DNSKEY,
+    pair: Pair,
+}
+
+/// A new private key for `algorithm` (ECDSA or Ed25519) as a PKCS#8 document, for
+/// `SigningKey::from_pkcs8()`
+pub fn generate_pkcs8(algorithm: u8) -> Result<Vec<u8>, Error> {
+    let rng = SystemRandom::new();
+    let pkcs8 = match algorithm {
+        ECDSAP256SHA256 | ECDSAP384SHA384 => {
+            EcdsaKeyPair::generate_pkcs8(ecdsa_algorithm(algorithm), &rng)
+        }
+        ED25519 => Ed25519KeyPair::generate_pkcs8(&rng),
+        algorithm => return Err(Error::UnsupportedAlgorithm(algorithm)),
+    };
+    pkcs8
+        .map(|pkcs8| pkcs8.as_ref().to_vec())
+        .map_err(|_| Error::InvalidKey)
+}
+
+fn ecdsa_algorithm(algorithm: u8) -> &'static EcdsaSigningAlgorithm {
+    if algorithm == ECDSAP256SHA256 {
+        &signature::ECDSA_P256_SHA256_FIXED_SIGNING
+    } else {
+        &signature::ECDSA_P384_SHA384_FIXED_SIGNING
+    }
+}
+
+impl SigningKey {
+    /// The key in a DER-encoded PKCS#8 document, for a DNSKEY record with `flags`
+    pub fn from_pkcs8(algorithm: u8, flags: u16, pkcs8: &[u8]) -> Result<SigningKey, Error> {
+        let rng = SystemRandom::new();
+        let (pair, public_key) = match algorithm {
+            RSASHA256 => {
+                let pair = RsaKeyPair::from_pkcs8(pkcs8).map_err(|_| Error::InvalidKey)?;
+                let public = PublicKeyComponents::<Vec<u8>>::from(pair.public());
+                // RFC 3110 section 2: the exponent's length, the exponent, the modulus
+                let mut public_key = match u8::try_from(public.e.len()) {
+                    Ok(len) => vec![len],
+                    Err(_) => {
+                        let len = (public.e.len() as u16).to_be_bytes();
+                        vec![0, len[0], len[1]]
+                    }
+                };
+                public_key.extend_from_slice(&public.e);
+                public_key.extend_from_slice(&public.n);
+                (Pair::Rsa(pair), public_key)
+            }
+            ECDSAP256SHA256 | ECDSAP384SHA384 => {
+                let pair = EcdsaKeyPair::from_pkcs8(ecdsa_algorithm(algorithm), pkcs8, &rng)
+                    .map_err(|_| Error::InvalidKey)?;
+                // Without the uncompressed point tag (RFC 6605)
+                let public_key = pair.public_key().as_ref()[1..].to_vec();
+                (Pair::Ecdsa(pair), public_key)
+            }
+            ED25519 => {
+                let pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8)
+                    .map_err(|_| Error::InvalidKey)?;
+                let public_key = pair.public_key().as_ref().to_vec();
+                (Pair::Ed25519(pair), public_key)
+            }
+            algorithm => return Err(Error::UnsupportedAlgorithm(algorithm)),
+        };
+        let dnskey = DNSKEY {
+            flags,
+            protocol: 3,
+            algorithm,
+            public_key,
+        };
+        Ok(SigningKey { dnskey, pair })
+    }
+
+    /// The key in the contents of BIND's `.key` file (the DNSKEY record) and `.private`
+    /// file (private key format v1.2 or later)
+    pub fn from_bind(key: &str, private: &str) -> Result<SigningKey, Error> {
+        let anchors = TrustAnchors::parse(key).map_err(|_| Error::InvalidKey)?;
+        let dnskey = match anchors.keys().collect::<Vec<_>>()[..] {
+            [dnskey] => dnskey.clone(),
+            _ => return Err(Error::InvalidKey),
+        };
+        let fields: Vec<(&str, &str)> = private
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .collect();
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, value)| *value)
+                .ok_or(Error::InvalidKey)
+        };
+        let decoded = |name: &str| {
+            field(name).and_then(|value| dnssec::decode_base64(value).ok_or(Error::InvalidKey))
+        };
+        let algorithm: u8 = field("Algorithm")?
+            .split_whitespace()
+            .next()
+            .and_then(|a| a.parse().ok())
+            .ok_or(Error::InvalidKey)?;
+        if algorithm != dnskey.algorithm {
+            return Err(Error::InvalidKey);
+        }
+
+        let rng = SystemRandom::new();
+        let pair = match algorithm {
+            RSASHA256 => {
+                let components = KeyPairComponents {
+                    public_key: PublicKeyComponents {
+                        n: decoded("Modulus")?,
+                        e: decoded("PublicExponent")?,
+                    },
+                    d: decoded("PrivateExponent")?,
+                    p: decoded("Prime1")?,
+                    q: decoded("Prime2")?,
+                    dP: decoded("Exponent1")?,
+                    dQ: decoded("Exponent2")?,
+                    qInv: decoded("Coefficient")?,
+                };
+                let unpadded = |b: &[u8]| b[b.iter().take_while(|&&b| b == 0).count()..].to_vec();
+                let (e, n) = dnssec::rsa_components(&dnskey.public_key)?;
+                if unpadded(&components.public_key.n) != unpadded(n)
+                    || unpadded(&components.public_key.e) != unpadded(e)
+                {
+                    return Err(Error::InvalidKey);
+                }
+                RsaKeyPair::from_components(&components).map(Pair::Rsa)
+            }
+            ECDSAP256SHA256 | ECDSAP384SHA384 => {
+                let mut point = vec![4];
+                point.extend_from_slice(&dnskey.public_key);
+                EcdsaKeyPair::from_private_key_and_public_key(
+                    ecdsa_algorithm(algorithm),
+                    &decoded("PrivateKey")?,
+                    &point,
+                    &rng,
+                )
+                .map(Pair::Ecdsa)
+            }
+            ED25519 => Ed25519KeyPair::from_seed_and_public_key(
+                &decoded("PrivateKey")?,
+                &dnskey.public_key,
+            )
+            .map(Pair::Ed25519),
+            algorithm => return Err(Error::UnsupportedAlgorithm(algorithm)),
+        };
+        let pair = pair.map_err(|_| Error::InvalidKey)?;
+        Ok(SigningKey { dnskey, pair })
+    }
+
+    /// Read the key from BIND's `.key` and `.private` files, as `from_bind()` does
+    pub fn from_bind_files<P: AsRef<Path>, Q: AsRef<Path>>(
+        key: P,
+        private: Q,
+    ) -> Result<SigningKey, Error> {
+        SigningKey::from_bind(&fs::read_to_string(key)?, &fs::read_to_string(private)?)
+    }
+
+    pub fn dnskey(&self) -> &DNSKEY {
+        &self.dnskey
+    }
+
+    /// Sign `data`, giving the signature as an RRSIG record holds it
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let rng = SystemRandom::new();
+        match self.pair {
+            Pair::Rsa(ref pair) => {
+                let mut signature = vec![0; pair.public().modulus_len()];
+                pair.sign(&signature::RSA_PKCS1_SHA256, &rng, data, &mut signature)
+                    .map_err(|_| Error::InvalidKey)?;
+                Ok(signature)
+            }
+            Pair::Ecdsa(ref pair) => pair
+                .sign(&rng, data)
+                .map(|signature| signature.as_ref().to_vec())
+                .map_err(|_| Error::InvalidKey),
+            Pair::Ed25519(ref pair) => Ok(pair.sign(data).as_ref().to_vec()),
+        }
+    }
+}
+
+/// How the signed zone proves that names and types do not exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chain {
+    Nsec,
+    /// NSEC3 with SHA-1 (RFC 5155).  With opt-out, delegations without DS records are
+    /// left out of the chain.
+    Nsec3 {
+        iterations: u16,
+        salt: Vec<u8>,
+        opt_out: bool,
+    },
+}
+
+/// Signs zones with a set of keys
+#[derive(Debug)]
+pub struct Signer {
+    keys: Vec<SigningKey>,
+    inception: SystemTime,
+    expiration: SystemTime,
+    chain: Chain,
+}
+
+// The records at one name, by type
+struct Node<'a> {
+    labels: Vec<Vec<u8>>,
+    rrsets: Vec<Vec<&'a ZoneRecord>>,
+}
+
+impl Node<'_> {
+    fn has(&self, rr_type: u16) -> bool {
+        self.rrsets.iter().any(|rrset| rrset[0].rr_type == rr_type)
+    }
+
+    fn types(&self) -> Vec<u16> {
+        self.rrsets.iter().map(|rrset| rrset[0].rr_type).collect()
+    }
+}
+
+impl Signer {
+    /// Sign with `keys`, proving denial with NSEC records, and with signatures valid
+    /// from an hour ago for 30 days
+    pub fn new(keys: Vec<SigningKey>) -> Signer {
+        let now = SystemTime::now();
+        Signer {
+            keys,
+            inception: now - Duration::from_secs(3600),
+            expiration: now + Duration::from_secs(30 * 86400),
+            chain: Chain::Nsec,
+        }
+    }
+
+    /// Make signatures valid from `inception` until `expiration`
+    pub fn validity(mut self, inception: SystemTime, expiration: SystemTime) -> Signer {
+        self.inception = inception;
+        self.expiration = expiration;
+        self
+    }
+
+    /// Make signatures valid from an hour ago until `period` from now
+    pub fn valid_for(self, period: Duration) -> Signer {
+        let now = SystemTime::now();
+        self.validity(now - Duration::from_secs(3600), now + period)
+    }
+
+    pub fn chain(mut self, chain: Chain) -> Signer {
+        self.chain = chain;
+        self
+    }
+
+    /// Sign the zone `zone` with records `records`, which must include the SOA record.
+    /// Returns them in canonical order, each RRSIG after the RRset it covers, with the
+    /// keys' DNSKEY records and the denial chain added and any existing RRSIG, NSEC,
+    /// NSEC3 and NSEC3PARAM records replaced.
+    pub fn sign_zone(&self, zone: &str, records: &[ZoneRecord]) -> Result<Vec<ZoneRecord>, Error> {
+        if self.keys.is_empty() {
+            return Err(Error::InvalidZone);
+        }
+        let apex = wire::labels(zone.as_bytes())?;
+        let zone = wire::name_to_string(&apex);
+        let mut entries: Vec<(Vec<Vec<u8>>, ZoneRecord)> = Vec::with_capacity(records.len());
+        for record in records {
+            if [RRSIG_TYPE, NSEC_TYPE, NSEC3_TYPE, NSEC3PARAM_TYPE].contains(&record.rr_type) {
+                continue;
+            }
+            let labels = wire::labels(record.name.as_bytes())?;
+            if !wire::is_subdomain(&labels, &apex) {
+                return Err(Error::InvalidZone);
+            }
+            let record = ZoneRecord {
+                name: wire::name_to_string(&labels),
+                ..record.clone()
+            };
+            entries.push((labels, record));
+        }
+        let at_apex = |entry: &&(Vec<Vec<u8>>, ZoneRecord), rr_type: u16| {
+            entry.1.rr_type == rr_type && wire::names_equal(&entry.0, &apex)
+        };
+        let soa = &entries
+            .iter()
+            .find(|e| at_apex(e, SOA_TYPE))
+            .ok_or(Error::InvalidZone)?
+            .1;
+        let (class, soa_ttl) = (soa.class, soa.ttl);
+        // RFC 9077: denial records last no longer than the zone's negative TTL
+        let negative_ttl = soa_ttl.min(soa.to_record::<SOA>()?.data.minimum);
+        let dnskey_ttl = entries
+            .iter()
+            .find(|e| at_apex(e, DNSKEY_TYPE))
+            .map_or(soa_ttl, |e| e.1.ttl);
+
+        let mut added = Vec::new();
+        for key in &self.keys {
+            let rdata = key.dnskey.to_rdata();
+            if !entries
+                .iter()
+                .any(|e| at_apex(&e, DNSKEY_TYPE) && e.1.rdata == rdata)
+            {
+                added.push(ZoneRecord {
+                    name: zone.clone(),
+                    class,
+                    ttl: dnskey_ttl,
+                    rr_type: DNSKEY_TYPE,
+                    rdata,
+                });
+            }
+        }
+        if let Chain::Nsec3 {
+            iterations,
+            ref salt,
+            ..
+        } = self.chain
+        {
+            let param = NSEC3PARAM {
+                hash_algorithm: NSEC3_SHA1,
+                flags: 0,
+                iterations,
+                salt: salt.clone(),
+            };
+            added.push(ZoneRecord {
+                name: zone.clone(),
+                class,
+                ttl: soa_ttl,
+                rr_type: NSEC3PARAM_TYPE,
+                rdata: param.to_rdata(),
+            });
+        }
+        entries.extend(added.into_iter().map(|record| (apex.clone(), record)));
+        entries.sort_by(|a, b| {
+            wire::canonical_cmp(&a.0, &b.0)
+                .then(a.1.rr_type.cmp(&b.1.rr_type))
+                .then_with(|| a.1.rdata.cmp(&b.1.rdata))
+        });
+        entries.dedup_by(|a, b| {
+            wire::names_equal(&a.0, &b.0) && a.1.rr_type == b.1.rr_type && a.1.rdata == b.1.rdata
+        });
+
+        let mut nodes: Vec<Node> = Vec::new();
+        for (labels, record) in &entries {
+            match nodes.last_mut() {
+                Some(node) if wire::names_equal(&node.labels, labels) => {
+                    match node.rrsets.last_mut() {
+                        Some(rrset) if rrset[0].rr_type == record.rr_type => rrset.push(record),
+                        _ => node.rrsets.push(vec![record]),
+                    }
+                }
+                _ => nodes.push(Node {
+                    labels: labels.clone(),
+                    rrsets: vec![vec![record]],
+                }),
+            }
+        }
+        let is_cut = |node: &Node| node.labels.len() > apex.len() && node.has(NS);
+        // Canonical order puts each name after its ancestors, so cuts are seen before
+        // the names below them.
+        let mut boundaries: Vec<&[Vec<u8>]> = Vec::new();
+        let mut authoritative: Vec<&Node> = Vec::new();
+        for node in &nodes {
+            if boundaries
+                .iter()
+                .any(|b| node.labels.len() > b.len() && wire::is_subdomain(&node.labels, b))
+            {
+                continue;
+            }
+            if is_cut(node) || node.has(DNAME) {
+                boundaries.push(&node.labels);
+            }
+            authoritative.push(node);
+        }
+
+        let chain = match self.chain {
+            Chain::Nsec => nsec_chain(&authoritative, class, negative_ttl)?,
+            Chain::Nsec3 {
+                iterations,
+                ref salt,
+                opt_out,
+            } => {
+                let mut names = Vec::new();
+                for node in &authoritative {
+                    let insecure = is_cut(node) && !node.has(DS);
+                    if opt_out && insecure {
+                        continue;
+                    }
+                    let mut types = node.types();
+                    if !insecure {
+                        types.push(RRSIG_TYPE);
+                    }
+                    names.push((node.labels.clone(), types));
+                }
+                let nsec3 = NSEC3 {
+                    hash_algorithm: NSEC3_SHA1,
+                    flags: if opt_out { NSEC3::FLAG_OPT_OUT } else { 0 },
+                    iterations,
+                    salt: salt.clone(),
+                    next_hashed_owner: Vec::new(),
+                    types: Vec::new(),
+                };
+                nsec3_chain(&apex, names, nsec3, class, negative_ttl)?
+            }
+        };
+
+        let mut signed = Vec::new();
+        for node in &authoritative {
+            for rrset in &node.rrsets {
+                // The parent signs only the DS RRset at a delegation.
+                if is_cut(node) && rrset[0].rr_type != DS {
+                    continue;
+                }
+                signed.extend(self.sign_rrset(&zone, &node.labels, rrset)?);
+            }
+        }
+        for record in &chain {
+            let labels = wire::labels(record.name.as_bytes())?;
+            signed.extend(self.sign_rrset(&zone, &labels, &[record])?);
+        }
+
+        let mut out: Vec<(Vec<Vec<u8>>, ZoneRecord)> = entries;
+        for record in chain.into_iter().chain(signed) {
+            out.push((wire::labels(record.name.as_bytes())?, record));
+        }
+        // Each RRSIG goes after the RRset it covers.
+        let order = |r: &ZoneRecord| match r.rr_type {
+            RRSIG_TYPE => (u16::from_be_bytes([r.rdata[0], r.rdata[1]]), true),
+            rr_type => (rr_type, false),
+        };
+        out.sort_by(|a, b| {
+            wire::canonical_cmp(&a.0, &b.0)
+                .then(order(&a.1).cmp(&order(&b.1)))
+                .then_with(|| a.1.rdata.cmp(&b.1.rdata))
+        });
+        Ok(out.into_iter().map(|(_, record)| record).collect())
+    }
+
+    // The RRSIGs of an RRset, by the keys that sign its type
+    fn sign_rrset(
+        &self,
+        zone: &str,
+        labels: &[Vec<u8>],
+        rrset: &[&ZoneRecord],
+    ) -> Result<Vec<ZoneRecord>, Error> {
+        let first = rrset[0];
+        let key_signing = first.rr_type == DNSKEY_TYPE;
+        let of_kind = |key: &&SigningKey| key.dnskey.is_secure_entry_point() == key_signing;
+        let keys: Vec<&SigningKey> = if self.keys.iter().any(|key| of_kind(&key)) {
+            self.keys.iter().filter(of_kind).collect()
+        } else {
+            self.keys.iter().collect()
+        };
+        let wildcard = labels.first().is_some_and(|label| label == b"*");
+        let records = rrset
+            .iter()
+            .map(|r| Ok((r.name.clone(), r.class as u16, r.canonical_rdata()?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut out = Vec::with_capacity(keys.len());
+        for key in keys {
+            let mut rrsig = RRSIG {
+                type_covered: first.rr_type,
+                algorithm: key.dnskey.algorithm,
+                labels: (labels.len() - usize::from(wildcard)) as u8,
+                original_ttl: first.ttl,
+                expiration: serial(self.expiration),
+                inception: serial(self.inception),
+                key_tag: key.dnskey.key_tag(),
+                signer_name: zone.to_owned(),
+                signature: Vec::new(),
+            };
+            rrsig.signature = key.sign(&rrsig.signed_data_raw(records.clone())?)?;
+            out.push(ZoneRecord {
+                name: first.name.clone(),
+                class: first.class,
+                ttl: first.ttl,
+                rr_type: RRSIG_TYPE,
+                rdata: rrsig.canonical_rdata()?,
+            });
+        }
+        Ok(out)
+    }
+}
+
+// Seconds since the epoch, modulo 2^32
+fn serial(time: SystemTime) -> u32 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as u32)
+}
+
+// An NSEC record at each authoritative name, pointing to the next, and the last back to
+// the apex
+fn nsec_chain(nodes: &[&Node], class: Class, ttl: u32) -> Result<Vec<ZoneRecord>, Error> {
+    let mut chain = Vec::with_capacity(nodes.len());
+    for (i, node) in nodes.iter().enumerate() {
+        let next = nodes.get(i + 1).unwrap_or(&nodes[0]);
+        let mut types = node.types();
+        types.extend([RRSIG_TYPE, NSEC_TYPE]);
+        types.sort_unstable();
+        let nsec = NSEC {
+            next_domain_name: wire::name_to_string(&next.labels),
+            types,
+        };
+        chain.push(ZoneRecord {
+            name: wire::name_to_string(&node.labels),
+            class,
+            ttl,
+            rr_type: NSEC_TYPE,
+            rdata: nsec.to_rdata()?,
+        });
+    }
+    Ok(chain)
+}
+
+// NSEC3 records for `names` (with their types) and the empty non-terminals above them,
+// in hash order, taking the parameters from `template`
+fn nsec3_chain(
+    apex: &[Vec<u8>],
+    mut names: Vec<(Vec<Vec<u8>>, Vec<u16>)>,
+    template: NSEC3,
+    class: Class,
+    ttl: u32,
+) -> Result<Vec<ZoneRecord>, Error> {
+    let lower = |labels: &[Vec<u8>]| -> Vec<Vec<u8>> {
+        labels.iter().map(|l| l.to_ascii_lowercase()).collect()
+    };
+    let mut seen: HashSet<Vec<Vec<u8>>> = names.iter().map(|(labels, _)| lower(labels)).collect();
+    for i in 0..names.len() {
+        let depth = names[i].0.len();
+        for start in 1..depth - apex.len() {
+            let ancestor = lower(&names[i].0[start..]);
+            if seen.insert(ancestor.clone()) {
+                names.push((ancestor, Vec::new()));
+            }
+        }
+    }
+
+    let mut hashed = names
+        .into_iter()
+        .map(|(labels, mut types)| {
+            types.sort_unstable();
+            let name = wire::name_to_string(&labels);
+            Ok((template.hash(&name)?, types))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    hashed.sort();
+    if hashed.windows(2).any(|w| w[0].0 == w[1].0) {
+        // A hash collision: RFC 5155 section 7.1 says to choose another salt.
+        return Err(Error::InvalidZone);
+    }
+    let mut chain = Vec::with_capacity(hashed.len());
+    for (i, (hash, types)) in hashed.iter().enumerate() {
+        let next = &hashed[(i + 1) % hashed.len()].0;
+        let nsec3 = NSEC3 {
+            next_hashed_owner: next.clone(),
+            types: types.clone(),
+            ..template.clone()
+        };
+        let mut owner = vec![dnssec::base32hex(hash).into_bytes()];
+        owner.extend_from_slice(apex);
+        chain.push(ZoneRecord {
+            name: wire::name_to_string(&owner),
+            class,
+            ttl,
+            rr_type: NSEC3_TYPE,
+            rdata: nsec3.to_rdata(),
+        });
+    }
+    Ok(chain)
+}
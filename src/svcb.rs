@@ -0,0 +1,145 @@
+//! Connection endpoints from SVCB and HTTPS records (RFC 9460): which hosts, ports and
+//! protocols to try, in order, with any Encrypted Client Hello configuration.
+//!
+//! Records of equal priority are kept in the order they came in; RFC 9460 asks clients
+//! to shuffle them, which is left to the caller.
+
+use crate::chain::MAX_CHAIN_LEN;
+use crate::error::Error;
+use crate::record::{SvcParam, HTTPS, SVCB};
+use crate::{wire, Class, Resolver};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str;
+
+/// The default protocol of HTTPS records
+pub const HTTP_1_1: &str = "http/1.1";
+
+/// Where and how to connect to a service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub priority: u16,
+    /// The host to connect to, whose addresses are looked up as usual
+    pub target: String,
+    pub port: u16,
+    /// Protocols to offer, the scheme's default last
+    pub alpn: Vec<String>,
+    /// The ECHConfigList for TLS Encrypted Client Hello
+    pub ech: Option<Vec<u8>>,
+    /// Addresses to try while the target's own are looked up
+    pub ipv4_hints: Vec<Ipv4Addr>,
+    pub ipv6_hints: Vec<Ipv6Addr>,
+}
+
+/// The endpoints the service-mode records of `rrset`, owned by `owner`, give, in order of
+/// priority.  Records with mandatory keys this crate does not know are skipped, and an
+/// RRset with an alias-mode record gives none; see `alias_target()`.
+pub fn endpoints<T: AsRef<SVCB>>(owner: &str, rrset: &[T], default_port: u16) -> Vec<Endpoint> {
+    collect(owner, rrset, default_port, None)
+}
+
+/// As `endpoints()`, for HTTPS records: port 443, and "http/1.1" unless a record has
+/// `no-default-alpn`
+pub fn https_endpoints(owner: &str, rrset: &[HTTPS]) -> Vec<Endpoint> {
+    collect(owner, rrset, 443, Some(HTTP_1_1))
+}
+
+/// The target of the alias-mode record of `rrset`, if it has one.  "." means the service
+/// is not available.
+pub fn alias_target<T: AsRef<SVCB>>(rrset: &[T]) -> Option<&str> {
+    rrset
+        .iter()
+        .map(AsRef::as_ref)
+        .find(|r| r.is_alias())
+        .map(|r| r.target.as_str())
+}
+
+fn collect<T: AsRef<SVCB>>(
+    owner: &str,
+    rrset: &[T],
+    default_port: u16,
+    default_alpn: Option<&str>,
+) -> Vec<Endpoint> {
+    if alias_target(rrset).is_some() {
+        return Vec::new();
+    }
+    let mut endpoints: Vec<Endpoint> = rrset
+        .iter()
+        .map(AsRef::as_ref)
+        .filter(|r| usable(r))
+        .map(|r| {
+            let mut alpn = r.alpn().to_vec();
+            if let Some(default) = default_alpn.filter(|_| !r.no_default_alpn()) {
+                if !alpn.iter().any(|id| id == default) {
+                    alpn.push(default.to_owned());
+                }
+            }
+            Endpoint {
+                priority: r.priority,
+                target: if r.target == "." {
+                    owner.to_owned()
+                } else {
+                    r.target.clone()
+                },
+                port: r.port().unwrap_or(default_port),
+                alpn,
+                ech: r.ech().map(<[u8]>::to_vec),
+                ipv4_hints: r.ipv4_hints().to_vec(),
+                ipv6_hints: r.ipv6_hints().to_vec(),
+            }
+        })
+        .collect();
+    endpoints.sort_by_key(|e| e.priority);
+    endpoints
+}
+
+// Whether a client may use a service-mode record: every mandatory key is one this crate
+// knows, other than "mandatory" itself, and is present (RFC 9460 section 8)
+fn usable(record: &SVCB) -> bool {
+    record.mandatory().iter().all(|&key| {
+        key != SvcParam::MANDATORY && key <= SvcParam::IPV6HINT && record.param(key).is_some()
+    })
+}
+
+impl Resolver {
+    /// The endpoints of the HTTPS service at `name`, following alias-mode records and
+    /// CNAMEs.  If an alias leads to a name without HTTPS records, that name is the one
+    /// endpoint, on port 443.  Empty if `name` has no HTTPS records or the service is not
+    /// available, in which case the client connects to `name` as it would without them.
+    pub fn https_endpoints(&mut self, name: &[u8]) -> Result<Vec<Endpoint>, Error> {
+        let mut name = str::from_utf8(name)?.to_owned();
+        let mut seen: Vec<Vec<Vec<u8>>> = Vec::new();
+        loop {
+            let labels = wire::labels(name.as_bytes())?;
+            if seen.iter().any(|s| wire::names_equal(s, &labels)) {
+                return Err(Error::AliasLoop);
+            }
+            if seen.len() > MAX_CHAIN_LEN {
+                return Err(Error::AliasChainTooLong);
+            }
+            seen.push(labels);
+
+            let resolution = match self.resolve::<HTTPS>(name.as_bytes(), Class::IN) {
+                Ok(resolution) => resolution,
+                Err(ref e) if wire::is_negative(e) && seen.len() > 1 => {
+                    return Ok(vec![Endpoint {
+                        priority: 1,
+                        target: name,
+                        port: 443,
+                        alpn: vec![HTTP_1_1.to_owned()],
+                        ech: None,
+                        ipv4_hints: Vec::new(),
+                        ipv6_hints: Vec::new(),
+                    }]);
+                }
+                Err(ref e) if wire::is_negative(e) => return Ok(Vec::new()),
+                Err(e) => return Err(e),
+            };
+            let rrset: Vec<HTTPS> = resolution.records.into_iter().map(|r| r.data).collect();
+            match alias_target(&rrset) {
+                Some(".") => return Ok(Vec::new()),
+                Some(target) => name = target.to_owned(),
+                None => return Ok(https_endpoints(&resolution.name, &rrset)),
+            }
+        }
+    }
+}
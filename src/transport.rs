@@ -0,0 +1,462 @@
+//! A DNS client transport that sends queries itself, over UDP and TCP, rather than
+//! through libresolv.
+//!
+//! It follows the same configuration as `Resolver` (name servers, search list, `ndots`,
+//! `timeout`, `attempts` and the `rotate`, `use-vc`, `edns0` and `trust-ad` options) and
+//! returns the same `Response`, but gives the caller control of timeouts and works with
+//! IPv6 name servers and any port.
+//!
+//! Each query gets a random ID and a fresh socket on an ephemeral port chosen (randomly,
+//! on modern kernels) by the operating system.  Responses whose source, ID or question
+//! do not match the query are ignored.  Truncated UDP responses are retried over TCP.
+//!
+//! With `set_cookies()`, DNS cookies are sent to and checked with each server; see the
+//! `cookie` module.
+
+use crate::cookie::{Cookies, Verdict, MAX_COOKIE_ATTEMPTS};
+use crate::edns::{self, Edns, EdnsOption};
+use crate::error::{Error, ResolutionError};
+use crate::wire::{self, Header};
+use crate::{Class, RecordType, ResolverConfig, ResolverOption, Response};
+use byteorder::{BigEndian, ByteOrder};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// UDP payload size advertised with EDNS(0), as recommended by DNS Flag Day 2020
+pub const EDNS_UDP_SIZE: u16 = 1232;
+
+// Response codes after which the next server is tried
+const RCODE_NOTIMP: u16 = 4;
+const RCODE_REFUSED: u16 = 5;
+
+pub struct Transport {
+    nameservers: Vec<SocketAddr>,
+    search: Vec<String>,
+    ndots: u8,
+    timeout: Duration,
+    attempts: u32,
+    options: Vec<ResolverOption>,
+    edns_options: Vec<EdnsOption>,
+    cookies: Option<Cookies>,
+    // Index of the server to try first, advanced after each query when rotating
+    next: usize,
+}
+
+impl Transport {
+    /// Create a transport configured from `/etc/resolv.conf`
+    pub fn new() -> Result<Transport, Error> {
+        Ok(Transport::from_config(&ResolverConfig::system()?))
+    }
+
+    /// Create a transport from the given configuration.  If it lists no name servers, the
+    /// local host is queried.
+    pub fn from_config(config: &ResolverConfig) -> Transport {
+        let mut nameservers = config.nameservers.clone();
+        if nameservers.is_empty() {
+            nameservers.push(SocketAddr::from((
+                Ipv4Addr::LOCALHOST,
+                crate::config::NAMESERVER_PORT,
+            )));
+        }
+        let mut transport = Transport {
+            nameservers,
+            search: config.search.clone(),
+            ndots: config.ndots,
+            timeout: Duration::from_secs(u64::from(config.timeout.max(1))),
+            attempts: config.attempts.max(1),
+            options: config.options.clone(),
+            edns_options: Vec::new(),
+            cookies: None,
+            next: 0,
+        };
+        transport.option(ResolverOption::Default, true);
+        transport
+    }
+
+    /// Set how long to wait for each name server to answer, overriding the configured
+    /// whole number of seconds
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Set or unset an option.  Only `Recurse`, `Rotate`, `UseVC`, `IgnTc`, `UseEDNS0`,
+    /// `UseDNSSEC` and `TrustAD` affect this transport.  `Default`, which is set to begin
+    /// with, stands for `Recurse`, `DefNames` and `DNSrch`.
+    pub fn option(&mut self, option: ResolverOption, value: bool) {
+        if option == ResolverOption::Default {
+            for &o in &[
+                ResolverOption::Recurse,
+                ResolverOption::DefNames,
+                ResolverOption::DNSrch,
+            ] {
+                self.option(o, value);
+            }
+            return;
+        }
+        self.options.retain(|o| *o != option);
+        if value {
+            self.options.push(option);
+        }
+    }
+
+    /// Send these EDNS options with each query.  See the `edns` module.
+    pub fn set_edns_options(&mut self, options: Vec<EdnsOption>) {
+        self.edns_options = options;
+    }
+
+    /// Send DNS cookies made with `cookies`, or none
+    pub fn set_cookies(&mut self, cookies: Option<Cookies>) {
+        self.cookies = cookies;
+    }
+
+    /// The cookies in use, with the server cookies learnt so far
+    pub fn cookies(&self) -> Option<&Cookies> {
+        self.cookies.as_ref()
+    }
+
+    pub(crate) fn has_option(&self, option: ResolverOption) -> bool {
+        self.options.contains(&option)
+    }
+
+    /// Lookup the record.  Does not apply the search algorithm, so `dname` must be a
+    /// complete domain name.  Fails with the same errors as `Resolver::query()`, or
+    /// `Error::Timeout` if no server answered.
+    pub fn query(
+        &mut self,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+    ) -> Result<Response, Error> {
+        let answer = self.query_answer(dname, class, typ)?;
+        edns::check_rcode(&answer)?;
+        Response::from_buffer(answer)
+    }
+
+    // As `query()`, but NXDOMAIN and NODATA answers are returned rather than errors.
+    pub(crate) fn query_answer(
+        &mut self,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+    ) -> Result<Vec<u8>, Error> {
+        let message = self.make_query(dname, class, typ)?;
+        let answer = self.send(&message)?;
+        match wire::check_rcode(&answer) {
+            Err(e) if !wire::is_negative(&e) => Err(edns::with_extended_errors(e, &answer)),
+            _ => Ok(answer),
+        }
+    }
+
+    /// Lookup the record, completing the name with the search list the way
+    /// `res_nsearch` does (but without consulting the hosts file).
+    pub fn search(
+        &mut self,
+        name: &[u8],
+        class: Class,
+        typ: RecordType,
+    ) -> Result<Response, Error> {
+        let answer = self.search_answer(name, class, typ)?;
+        edns::check_rcode(&answer)?;
+        Response::from_buffer(answer)
+    }
+
+    // As `search()`, but if every candidate name fails with NXDOMAIN or NODATA, a NODATA
+    // answer if there was one, or else the last answer, is returned.
+    pub(crate) fn search_answer(
+        &mut self,
+        name: &[u8],
+        class: Class,
+        typ: RecordType,
+    ) -> Result<Vec<u8>, Error> {
+        let search = self.search.clone();
+        search_with(name, self.ndots, &search, |candidate| {
+            self.query_answer(candidate, class, typ)
+        })
+    }
+
+    /// Build a query message the way this transport would send it
+    pub fn make_query(
+        &self,
+        dname: &[u8],
+        class: Class,
+        typ: RecordType,
+    ) -> Result<Vec<u8>, Error> {
+        let mut flags = 0;
+        if self.has_option(ResolverOption::Recurse) {
+            flags |= wire::FLAG_RD;
+        }
+        if self.has_option(ResolverOption::TrustAD) {
+            flags |= wire::FLAG_AD;
+        }
+        let edns = Edns::for_query(
+            &self.edns_options,
+            self.has_option(ResolverOption::UseEDNS0),
+            self.has_option(ResolverOption::UseDNSSEC),
+        );
+        wire::build_query(
+            random_id(),
+            flags,
+            dname,
+            class as u16,
+            typ as u16,
+            edns.as_ref(),
+        )
+    }
+
+    /// Send a complete query message and return the raw response.  Each server is tried
+    /// in turn, `attempts` times over.  A server answering SERVFAIL, NOTIMP or REFUSED
+    /// is passed over for the next; if none does better, the last such answer is
+    /// returned.
+    pub fn send(&mut self, query: &[u8]) -> Result<Vec<u8>, Error> {
+        let id = Header::parse(query)?.id;
+        let question = wire::first_question(query)?;
+
+        let count = self.nameservers.len();
+        let first = self.next % count;
+        if self.has_option(ResolverOption::Rotate) {
+            self.next = (self.next + 1) % count;
+        }
+
+        let mut last_error = Error::Timeout;
+        let mut last_answer: Option<Vec<u8>> = None;
+        for _ in 0..self.attempts {
+            for i in 0..count {
+                let server = self.nameservers[(first + i) % count];
+                match self.exchange_cookies(server, query, id, &question) {
+                    Ok(answer) => {
+                        let rcode = Header::parse(&answer)?.flags & 0x000f;
+                        match rcode {
+                            wire::RCODE_SERVFAIL | RCODE_NOTIMP | RCODE_REFUSED => {
+                                last_answer = Some(answer)
+                            }
+                            _ => return Ok(answer),
+                        }
+                    }
+                    Err(e) => last_error = e,
+                }
+            }
+        }
+        last_answer.ok_or(last_error)
+    }
+
+    // Exchange the query with a server, with cookies if they are in use
+    fn exchange_cookies(
+        &mut self,
+        server: SocketAddr,
+        query: &[u8],
+        id: u16,
+        question: &Question,
+    ) -> Result<Vec<u8>, Error> {
+        let mut cookies = match self.cookies.take() {
+            Some(cookies) => cookies,
+            None => return self.exchange(server, query, id, question, None, false),
+        };
+        let mut result = Err(Error::Timeout);
+        for attempt in 1..=MAX_COOKIE_ATTEMPTS {
+            let cookie_query = edns::add_option(query, &cookies.option(server));
+            let tcp = attempt == MAX_COOKIE_ATTEMPTS;
+            result = cookie_query
+                .and_then(|q| self.exchange(server, &q, id, question, Some(&cookies), tcp));
+            match result {
+                Ok(ref answer) if cookies.learn(server, answer) == Verdict::Retry => {}
+                _ => break,
+            }
+        }
+        self.cookies = Some(cookies);
+        result
+    }
+
+    fn exchange(
+        &self,
+        server: SocketAddr,
+        query: &[u8],
+        id: u16,
+        question: &Question,
+        cookies: Option<&Cookies>,
+        tcp: bool,
+    ) -> Result<Vec<u8>, Error> {
+        let expected = &(question, server, cookies);
+        if !tcp && !self.has_option(ResolverOption::UseVC) {
+            let answer = self.exchange_udp(server, query, id, expected)?;
+            let flags = Header::parse(&answer)?.flags;
+            if flags & wire::FLAG_TC == 0 || self.has_option(ResolverOption::IgnTc) {
+                return Ok(answer);
+            }
+        }
+        self.exchange_tcp(server, query, id, expected)
+    }
+
+    fn exchange_udp(
+        &self,
+        server: SocketAddr,
+        query: &[u8],
+        id: u16,
+        expected: &Expected,
+    ) -> Result<Vec<u8>, Error> {
+        let local: SocketAddr = match server {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local)?;
+        // A connected socket only receives datagrams from the server.
+        socket.connect(server)?;
+        socket.send(query)?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut buffer = vec![0; 65535];
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::Timeout);
+            }
+            socket.set_read_timeout(Some(deadline - now))?;
+            let len = socket.recv(&mut buffer).map_err(io_error)?;
+            if matches(&buffer[..len], id, expected) {
+                buffer.truncate(len);
+                return Ok(buffer);
+            }
+        }
+    }
+
+    fn exchange_tcp(
+        &self,
+        server: SocketAddr,
+        query: &[u8],
+        id: u16,
+        expected: &Expected,
+    ) -> Result<Vec<u8>, Error> {
+        let deadline = Instant::now() + self.timeout;
+        let mut stream = TcpStream::connect_timeout(&server, self.timeout).map_err(io_error)?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let mut message: Vec<u8> = Vec::with_capacity(query.len() + 2);
+        wire::push_u16(&mut message, query.len() as u16);
+        message.extend_from_slice(query);
+        stream.write_all(&message).map_err(io_error)?;
+
+        // Each read waits only until the deadline, so that a stream of messages which do
+        // not match cannot keep the exchange going.
+        let mut read = |buffer: &mut [u8]| -> Result<(), Error> {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::Timeout);
+            }
+            stream.set_read_timeout(Some(deadline - now))?;
+            stream.read_exact(buffer).map_err(io_error)
+        };
+        loop {
+            let mut prefix = [0u8; 2];
+            read(&mut prefix)?;
+            let mut answer = vec![0; BigEndian::read_u16(&prefix) as usize];
+            read(&mut answer)?;
+            if matches(&answer, id, expected) {
+                return Ok(answer);
+            }
+        }
+    }
+}
+
+type Question = (Vec<Vec<u8>>, u16, u16);
+
+// The question, with the server asked and the cookies sent to it, if any
+type Expected<'a> = (&'a Question, SocketAddr, Option<&'a Cookies>);
+
+// A response answers our query if it is a response, has our ID, repeats our question and
+// carries our client cookie, if it has one.
+fn matches(answer: &[u8], id: u16, &(question, server, cookies): &Expected) -> bool {
+    let header = match Header::parse(answer) {
+        Ok(h) => h,
+        Err(_) => return false,
+    };
+    if header.flags & wire::FLAG_QR == 0 || header.id != id {
+        return false;
+    }
+    match wire::first_question(answer) {
+        Ok((name, typ, class)) => {
+            wire::names_equal(&name, &question.0)
+                && typ == question.1
+                && class == question.2
+                && cookies.is_none_or(|c| c.matches(server, answer))
+        }
+        Err(_) => false,
+    }
+}
+
+fn io_error(e: io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Error::Timeout,
+        _ => Error::from(e),
+    }
+}
+
+/// Apply the search list to `name` the way `res_nsearch` does, calling `query` for each
+/// candidate name until one succeeds.  `query` returns NXDOMAIN and NODATA answers rather
+/// than errors; if no candidate succeeds, a NODATA answer if there was one, or else the
+/// last answer, is returned.  A candidate failing with an error (such as SERVFAIL or a
+/// timeout) does not end the search; the last error is returned if every candidate fails.
+pub(crate) fn search_with<F>(
+    name: &[u8],
+    ndots: u8,
+    search: &[String],
+    mut query: F,
+) -> Result<Vec<u8>, Error>
+where
+    F: FnMut(&[u8]) -> Result<Vec<u8>, Error>,
+{
+    if name.last() == Some(&b'.') || search.is_empty() {
+        return query(name);
+    }
+
+    let dots = name.iter().filter(|&&c| c == b'.').count();
+    let as_is_first = dots >= ndots as usize;
+    let mut candidates: Vec<Vec<u8>> = Vec::new();
+    if as_is_first {
+        candidates.push(name.to_vec());
+    }
+    for domain in search {
+        let mut candidate = name.to_vec();
+        candidate.push(b'.');
+        candidate.extend_from_slice(domain.as_bytes());
+        candidates.push(candidate);
+    }
+    if !as_is_first {
+        candidates.push(name.to_vec());
+    }
+
+    let mut no_data: Option<Vec<u8>> = None;
+    let mut last: Option<Vec<u8>> = None;
+    let mut error = Error::Resolver(ResolutionError::HostNotFound);
+    for candidate in candidates {
+        let answer = match query(&candidate) {
+            Ok(answer) => answer,
+            Err(e) => {
+                error = e;
+                continue;
+            }
+        };
+        match wire::check_rcode(&answer) {
+            Ok(()) => return Ok(answer),
+            Err(Error::Resolver(ResolutionError::NoData)) => {
+                no_data.get_or_insert(answer);
+            }
+            Err(_) => last = Some(answer),
+        }
+    }
+    no_data.or(last).ok_or(error)
+}
+
+pub(crate) fn random_id() -> u16 {
+    random_u64() as u16
+}
+
+// RandomState is keyed from the operating system's random source.
+pub(crate) fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
@@ -0,0 +1,238 @@
+//! Checking that a response answers the query and holds nothing it should not.
+//!
+//! Nothing here is applied unless asked for with `Response::validate()`.  It checks the
+//! response's ID and question against the query, then scrubs the response the way
+//! resolvers defend against cache poisoning:
+//!
+//! * In the answer section, records that are neither owned by the name asked for nor by
+//!   a name in the CNAME/DNAME chain from it are reported as unrelated.  They are kept,
+//!   as `chain::Resolution` already ignores them.
+//! * In the authority section, SOA and NS records must be owned by the closest
+//!   enclosing zone of the name (or of a name in the chain): the longest of their owners
+//!   that is the name or one of its ancestors, and within the zone queried if
+//!   `Question::zone()` gives it.  These are the zones the response may speak for.
+//!   Other authority records must lie within one of those zones.
+//! * In the additional section, records must lie within one of those zones or be owned
+//!   by a name in the chain.  Without SOA or NS records, that leaves only records for the
+//!   names in the chain.  OPT records are always kept.
+//!
+//! Records that fail the authority and additional checks are discarded from the
+//! `Response`, so that they are no longer counted or returned, and reported.
+
+use crate::chain::MAX_CHAIN_LEN;
+use crate::error::Error;
+use crate::record::{RecordData, RecordType, Rr, CNAME, DNAME};
+use crate::{wire, Class, Response, Section};
+
+/// The query a response is expected to answer
+#[derive(Debug, Clone)]
+pub struct Question {
+    pub name: String,
+    pub class: Class,
+    pub typ: RecordType,
+    /// The query ID, if known.  libresolv checks IDs itself without reporting them.
+    pub id: Option<u16>,
+    /// The zone of the server queried, if known: the response may speak for no other
+    pub zone: Option<String>,
+}
+
+impl Question {
+    pub fn new(name: &str, class: Class, typ: RecordType) -> Question {
+        Question {
+            name: name.to_owned(),
+            class,
+            typ,
+            id: None,
+            zone: None,
+        }
+    }
+
+    /// Also check the response's ID
+    pub fn id(mut self, id: u16) -> Question {
+        self.id = Some(id);
+        self
+    }
+
+    /// Also check that the zones the response speaks for are within `zone`
+    pub fn zone(mut self, zone: &str) -> Question {
+        self.zone = Some(zone.to_owned());
+        self
+    }
+}
+
+/// A record found wanting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub section: Section,
+    /// Position in its section before anything was discarded
+    pub index: usize,
+    pub name: String,
+    pub rr_type: u16,
+}
+
+/// What `Response::validate()` found
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// Authority and additional records outside the zones the response speaks for,
+    /// which have been discarded
+    pub discarded: Vec<Finding>,
+    /// Answer records unrelated to the question, which have been kept
+    pub unrelated: Vec<Finding>,
+}
+
+impl Report {
+    /// Whether nothing was found wanting
+    pub fn is_clean(&self) -> bool {
+        self.discarded.is_empty() && self.unrelated.is_empty()
+    }
+}
+
+type Labels = Vec<Vec<u8>>;
+
+impl Response {
+    /// Check that this response answers `question`, and discard authority and additional
+    /// records from outside the zones it speaks for.  See the `validate` module for the
+    /// rules.  Fails with `Error::IdMismatch` or `Error::QuestionMismatch` if the response
+    /// is not an answer to the query at all.
+    pub fn validate(&mut self, question: &Question) -> Result<Report, Error> {
+        if question.id.is_some_and(|id| id != self.get_id()) {
+            return Err(Error::IdMismatch);
+        }
+        let qname = wire::labels(question.name.as_bytes())?;
+        if self.get_section_count(Section::Question) != 1 {
+            return Err(Error::QuestionMismatch);
+        }
+        let (name, typ, class) = wire::first_question(self.as_bytes())?;
+        if !wire::names_equal(&name, &qname)
+            || typ != question.typ as u16
+            || class != question.class as u16
+        {
+            return Err(Error::QuestionMismatch);
+        }
+
+        let names = self.chain_names(qname)?;
+        let in_chain = |owner: &Labels| names.iter().any(|n| wire::names_equal(n, owner));
+        let mut report = Report::default();
+
+        for (index, (rr, owner)) in self.owners(Section::Answer)?.into_iter().enumerate() {
+            let below_dname = rr.type_ == RecordType::DNAME as u16
+                && names
+                    .iter()
+                    .any(|n| n.len() > owner.len() && wire::is_subdomain(n, &owner));
+            if !in_chain(&owner) && !below_dname {
+                report.unrelated.push(finding(Section::Answer, index, &rr));
+            }
+        }
+
+        let authority = self.owners(Section::Authority)?;
+        let queried = match question.zone {
+            Some(ref zone) => Some(wire::labels(zone.as_bytes())?),
+            None => None,
+        };
+        let in_queried = |owner: &Labels| {
+            queried
+                .as_ref()
+                .is_none_or(|zone| wire::is_subdomain(owner, zone))
+        };
+        // The closest enclosing zone of each name in the chain
+        let zones: Vec<&Labels> = names
+            .iter()
+            .filter_map(|name| {
+                authority
+                    .iter()
+                    .filter(|(rr, owner)| {
+                        is_zone_record(rr) && wire::is_subdomain(name, owner) && in_queried(owner)
+                    })
+                    .map(|(_, owner)| owner)
+                    .max_by_key(|owner| owner.len())
+            })
+            .collect();
+        let is_zone = |owner: &Labels| zones.iter().any(|z| wire::names_equal(owner, z));
+        let in_zone = |owner: &Labels| zones.iter().any(|z| wire::is_subdomain(owner, z));
+
+        let mut keep_authority = Vec::new();
+        for (index, (rr, owner)) in authority.iter().enumerate() {
+            let keep = if is_zone_record(rr) {
+                is_zone(owner)
+            } else {
+                in_zone(owner)
+            };
+            if !keep {
+                report
+                    .discarded
+                    .push(finding(Section::Authority, index, rr));
+            }
+            keep_authority.push(keep);
+        }
+
+        let mut keep_additional = Vec::new();
+        for (index, (rr, owner)) in self.owners(Section::Additional)?.iter().enumerate() {
+            let keep = rr.type_ == RecordType::OPT as u16 || in_zone(owner) || in_chain(owner);
+            if !keep {
+                report
+                    .discarded
+                    .push(finding(Section::Additional, index, rr));
+            }
+            keep_additional.push(keep);
+        }
+
+        self.retain(Section::Authority, &keep_authority);
+        self.retain(Section::Additional, &keep_additional);
+        Ok(report)
+    }
+
+    // The records of a section with their owner names as labels
+    pub(crate) fn owners(&self, section: Section) -> Result<Vec<(Rr<'_>, Labels)>, Error> {
+        (0..self.get_section_count(section))
+            .map(|i| {
+                let rr = self.get_rr(section, i)?;
+                let owner = wire::labels(rr.name.as_bytes())?;
+                Ok((rr, owner))
+            })
+            .collect()
+    }
+
+    // The name asked for and the names its aliases in the answer section lead to.  Loops
+    // and overlong chains are cut short rather than reported here.
+    pub(crate) fn chain_names(&self, qname: Labels) -> Result<Vec<Labels>, Error> {
+        let answers = self.owners(Section::Answer)?;
+        let mut names = vec![qname];
+        while names.len() <= MAX_CHAIN_LEN {
+            let current = names.last().unwrap();
+            let mut target = None;
+            for (rr, owner) in &answers {
+                if rr.type_ == RecordType::DNAME as u16
+                    && current.len() > owner.len()
+                    && wire::is_subdomain(current, owner)
+                {
+                    let dname = DNAME::extract(rr)?;
+                    let mut labels = current[..current.len() - owner.len()].to_vec();
+                    labels.extend(wire::labels(dname.target.as_bytes())?);
+                    target = Some(labels);
+                    break;
+                }
+                if rr.type_ == RecordType::CNAME as u16 && wire::names_equal(owner, current) {
+                    target = Some(wire::labels(CNAME::extract(rr)?.cname.as_bytes())?);
+                }
+            }
+            match target {
+                Some(t) if !names.iter().any(|n| wire::names_equal(n, &t)) => names.push(t),
+                _ => break,
+            }
+        }
+        Ok(names)
+    }
+}
+
+fn is_zone_record(rr: &Rr) -> bool {
+    rr.type_ == RecordType::SOA as u16 || rr.type_ == RecordType::NS as u16
+}
+
+fn finding(section: Section, index: usize, rr: &Rr) -> Finding {
+    Finding {
+        section,
+        index,
+        name: rr.name.clone(),
+        rr_type: rr.type_,
+    }
+}
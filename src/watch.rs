@@ -0,0 +1,65 @@
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Identifies one version of a file: if any of these change, the file was rewritten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stamp {
+    dev: u64,
+    ino: u64,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Stamp> {
+        let meta = fs::metadata(path).ok()?;
+        Some(Stamp {
+            dev: meta.dev(),
+            ino: meta.ino(),
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+}
+
+/// Watches a configuration file for changes, the same way glibc does for the non-thread-safe
+/// resolver state: by comparing its `stat()` results before each query.  Replacing the file
+/// (as most tools that rewrite `resolv.conf` do) and editing it in place are both noticed.
+#[derive(Debug, Clone)]
+pub struct ConfigWatch {
+    path: PathBuf,
+    stamp: Option<Stamp>,
+}
+
+impl ConfigWatch {
+    pub fn new<P: AsRef<Path>>(path: P) -> ConfigWatch {
+        let path = path.as_ref().to_path_buf();
+        let stamp = Stamp::of(&path);
+        ConfigWatch { path, stamp }
+    }
+
+    /// Whether the file differs from when it was last polled.  The new version is
+    /// remembered before returning, so the caller should then re-read the file.  A file
+    /// that has disappeared counts as unchanged, so a resolver keeps its last good state
+    /// while the file is being replaced.
+    pub fn poll(&mut self) -> bool {
+        match Stamp::of(&self.path) {
+            Some(stamp) => {
+                if self.stamp.as_ref() == Some(&stamp) {
+                    return false;
+                }
+                self.stamp = Some(stamp);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget the last seen version, so the next poll reports a change.  Used when
+    /// re-reading the file failed and should be retried.
+    pub fn forget(&mut self) {
+        self.stamp = None;
+    }
+}
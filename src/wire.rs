@@ -0,0 +1,335 @@
+//! DNS wire format helpers for building and checking messages without libresolv.
+
+use crate::edns::Edns;
+use crate::error::{Error, ResolutionError};
+use byteorder::{BigEndian, ByteOrder};
+
+/// Size of the fixed message header
+pub const HEADER_LEN: usize = 12;
+/// Longest domain name in wire format
+pub const MAX_NAME_LEN: usize = 255;
+/// Longest label in a domain name
+pub const MAX_LABEL_LEN: usize = 63;
+
+/// Response codes
+pub const RCODE_SERVFAIL: u16 = 2;
+pub const RCODE_NXDOMAIN: u16 = 3;
+
+/// Header flag bits, as found in the second 16-bit word of the header
+pub const FLAG_QR: u16 = 0x8000;
+pub const FLAG_TC: u16 = 0x0200;
+pub const FLAG_RD: u16 = 0x0100;
+pub const FLAG_AD: u16 = 0x0020;
+
+/// The fixed message header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub id: u16,
+    pub flags: u16,
+    pub qdcount: u16,
+    pub ancount: u16,
+    pub nscount: u16,
+    pub arcount: u16,
+}
+
+impl Header {
+    pub fn parse(msg: &[u8]) -> Result<Header, Error> {
+        if msg.len() < HEADER_LEN {
+            return Err(Error::ParseError);
+        }
+        Ok(Header {
+            id: BigEndian::read_u16(&msg[0..2]),
+            flags: BigEndian::read_u16(&msg[2..4]),
+            qdcount: BigEndian::read_u16(&msg[4..6]),
+            ancount: BigEndian::read_u16(&msg[6..8]),
+            nscount: BigEndian::read_u16(&msg[8..10]),
+            arcount: BigEndian::read_u16(&msg[10..12]),
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        for word in &[
+            self.id,
+            self.flags,
+            self.qdcount,
+            self.ancount,
+            self.nscount,
+            self.arcount,
+        ] {
+            push_u16(out, *word);
+        }
+    }
+}
+
+pub fn push_u16(out: &mut Vec<u8>, value: u16) {
+    let mut bytes = [0; 2];
+    BigEndian::write_u16(&mut bytes, value);
+    out.extend_from_slice(&bytes);
+}
+
+pub fn push_u32(out: &mut Vec<u8>, value: u32) {
+    let mut bytes = [0; 4];
+    BigEndian::write_u32(&mut bytes, value);
+    out.extend_from_slice(&bytes);
+}
+
+/// Convert a domain name in presentation format (dot separated, with `\.` and `\DDD`
+/// escapes) to uncompressed wire format.  A trailing dot is optional.
+pub fn encode_name(name: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out: Vec<u8> = Vec::with_capacity(name.len() + 2);
+    let mut label: Vec<u8> = Vec::new();
+    let mut i = 0;
+
+    if name == b"." || name.is_empty() {
+        return Ok(vec![0]);
+    }
+
+    while i < name.len() {
+        match name[i] {
+            b'.' => {
+                if label.is_empty() {
+                    return Err(Error::InvalidName);
+                }
+                push_label(&mut out, &label)?;
+                label.clear();
+                i += 1;
+            }
+            b'\\' => {
+                let digits = name.get(i + 1..i + 4);
+                match digits {
+                    Some(d) if d.iter().all(|c| c.is_ascii_digit()) => {
+                        let value = d.iter().fold(0u32, |acc, c| acc * 10 + u32::from(c - b'0'));
+                        if value > 255 {
+                            return Err(Error::InvalidName);
+                        }
+                        label.push(value as u8);
+                        i += 4;
+                    }
+                    _ => match name.get(i + 1) {
+                        Some(c) => {
+                            label.push(*c);
+                            i += 2;
+                        }
+                        None => return Err(Error::InvalidName),
+                    },
+                }
+            }
+            c => {
+                label.push(c);
+                i += 1;
+            }
+        }
+    }
+    if !label.is_empty() {
+        push_label(&mut out, &label)?;
+    }
+    out.push(0);
+    if out.len() > MAX_NAME_LEN {
+        return Err(Error::InvalidName);
+    }
+    Ok(out)
+}
+
+fn push_label(out: &mut Vec<u8>, label: &[u8]) -> Result<(), Error> {
+    if label.len() > MAX_LABEL_LEN {
+        return Err(Error::InvalidName);
+    }
+    out.push(label.len() as u8);
+    out.extend_from_slice(label);
+    Ok(())
+}
+
+/// Read a possibly compressed domain name starting at `offset` in `msg`.  Returns the
+/// labels, and the offset just past the name where it appears (not where any
+/// compression pointer leads).
+pub fn read_name(msg: &[u8], offset: usize) -> Result<(Vec<Vec<u8>>, usize), Error> {
+    let mut labels: Vec<Vec<u8>> = Vec::new();
+    let mut pos = offset;
+    let mut end: Option<usize> = None;
+    let mut total = 0;
+    // Each pointer must lead strictly backwards, so this cannot loop.
+    let mut limit = offset;
+
+    loop {
+        let len = *msg.get(pos).ok_or(Error::UncompressError)? as usize;
+        match len & 0xc0 {
+            0x00 => {
+                if len == 0 {
+                    let end = end.unwrap_or(pos + 1);
+                    return Ok((labels, end));
+                }
+                let label = msg
+                    .get(pos + 1..pos + 1 + len)
+                    .ok_or(Error::UncompressError)?;
+                total += len + 1;
+                if total > MAX_NAME_LEN {
+                    return Err(Error::UncompressError);
+                }
+                labels.push(label.to_vec());
+                pos += len + 1;
+            }
+            0xc0 => {
+                let low = *msg.get(pos + 1).ok_or(Error::UncompressError)? as usize;
+                let target = ((len & 0x3f) << 8) | low;
+                if end.is_none() {
+                    end = Some(pos + 2);
+                }
+                if target >= limit {
+                    return Err(Error::UncompressError);
+                }
+                limit = target;
+                pos = target;
+            }
+            _ => return Err(Error::UncompressError),
+        }
+    }
+}
+
+/// Format labels in presentation format, the way `ns_name_ntop` does: no trailing dot
+/// (except for the root, which is "."), and special characters escaped.
+pub fn name_to_string(labels: &[Vec<u8>]) -> String {
+    if labels.is_empty() {
+        return ".".to_owned();
+    }
+    let mut out = String::new();
+    for (i, label) in labels.iter().enumerate() {
+        if i > 0 {
+            out.push('.');
+        }
+        for &c in label {
+            match c {
+                b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                    out.push('\\');
+                    out.push(c as char);
+                }
+                0x21..=0x7e => out.push(c as char),
+                _ => out.push_str(&format!("\\{:03}", c)),
+            }
+        }
+    }
+    out
+}
+
+/// A domain name in the canonical form of RFC 4034 section 6.2: uncompressed wire format,
+/// in lower case
+pub fn canonical_name(name: &str) -> Result<Vec<u8>, Error> {
+    Ok(encode_name(name.as_bytes())?.to_ascii_lowercase())
+}
+
+/// The labels of a domain name in presentation format
+pub fn labels(name: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    Ok(read_name(&encode_name(name)?, 0)?.0)
+}
+
+/// Whether `name` is `parent` or below it, comparing labels without regard to case
+pub fn is_subdomain(name: &[Vec<u8>], parent: &[Vec<u8>]) -> bool {
+    name.len() >= parent.len() && names_equal(&name[name.len() - parent.len()..], parent)
+}
+
+/// Order domain names canonically (RFC 4034 section 6.1): label by label from the
+/// root, comparing labels as lower-case octet strings
+#[cfg(feature = "dnssec")]
+pub fn canonical_cmp(a: &[Vec<u8>], b: &[Vec<u8>]) -> std::cmp::Ordering {
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        let order = x
+            .iter()
+            .map(u8::to_ascii_lowercase)
+            .cmp(y.iter().map(u8::to_ascii_lowercase));
+        if order != std::cmp::Ordering::Equal {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Case-insensitive comparison of domain names in label form
+pub fn names_equal(a: &[Vec<u8>], b: &[Vec<u8>]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.eq_ignore_ascii_case(y))
+}
+
+/// The first question of a message: name labels, type and class
+pub fn first_question(msg: &[u8]) -> Result<(Vec<Vec<u8>>, u16, u16), Error> {
+    let header = Header::parse(msg)?;
+    if header.qdcount == 0 {
+        return Err(Error::ParseError);
+    }
+    let (labels, end) = read_name(msg, HEADER_LEN)?;
+    let fixed = msg.get(end..end + 4).ok_or(Error::ParseError)?;
+    Ok((
+        labels,
+        BigEndian::read_u16(&fixed[0..2]),
+        BigEndian::read_u16(&fixed[2..4]),
+    ))
+}
+
+/// Build a query message with one question, and an OPT record if `edns` is given
+pub fn build_query(
+    id: u16,
+    flags: u16,
+    name: &[u8],
+    class: u16,
+    typ: u16,
+    edns: Option<&Edns>,
+) -> Result<Vec<u8>, Error> {
+    let qname = encode_name(name)?;
+    let mut out: Vec<u8> = Vec::with_capacity(HEADER_LEN + qname.len() + 4 + 11);
+    Header {
+        id,
+        flags,
+        qdcount: 1,
+        ancount: 0,
+        nscount: 0,
+        arcount: 0,
+    }
+    .write(&mut out);
+    out.extend_from_slice(&qname);
+    push_u16(&mut out, typ);
+    push_u16(&mut out, class);
+    if let Some(edns) = edns {
+        edns.write(&mut out);
+    }
+    Ok(out)
+}
+
+/// Interpret the response code and answer count the way `res_nquery` does
+pub fn check_rcode(answer: &[u8]) -> Result<(), Error> {
+    let header = Header::parse(answer)?;
+    let error = match header.flags & 0x000f {
+        0 if header.ancount == 0 => ResolutionError::NoData,
+        0 => return Ok(()),
+        RCODE_NXDOMAIN => ResolutionError::HostNotFound,
+        RCODE_SERVFAIL => ResolutionError::TryAgain,
+        _ => ResolutionError::NoRecovery,
+    };
+    Err(Error::Resolver(error))
+}
+
+/// Whether an error from `check_rcode()` is an answer that the name or data does not
+/// exist, rather than a failure to get an answer
+pub fn is_negative(error: &Error) -> bool {
+    matches!(
+        error.resolution_error(),
+        Some(ResolutionError::HostNotFound) | Some(ResolutionError::NoData)
+    )
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode `data` in base64 with padding, as DNSKEY and RRSIG records are presented
+pub fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, &b)| bits | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(bits >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,138 @@
+//! Zone digests (RFC 8976): a ZONEMD record at the apex of a zone holds a digest of all
+//! its records, so a copy can be checked after transfer.  Only the SIMPLE scheme is
+//! supported.  Requires the `dnssec` feature.
+//!
+//! The apex ZONEMD RRset and its signatures are left out of the digest, but a signed zone
+//! must have a ZONEMD record when it is signed, for the denial chain to list its type.
+//! So: add a placeholder with `zonemd()`, sign the zone, then replace the placeholder with
+//! `zonemd()` again and sign that RRset.
+
+use crate::error::Error;
+use crate::record::{RecordType, SOA, ZONEMD};
+use crate::sign::ZoneRecord;
+use crate::wire;
+use ring::digest;
+use std::convert::TryFrom;
+
+/// The SIMPLE scheme: one digest over the whole zone
+pub const SIMPLE: u8 = 1;
+
+/// Hash algorithms
+pub const SHA384: u8 = 1;
+pub const SHA512: u8 = 2;
+
+const ZONEMD_TYPE: u16 = RecordType::ZONEMD as u16;
+const RRSIG: u16 = RecordType::RRSIG as u16;
+
+/// The SIMPLE digest of `records`, the records of `zone` (RFC 8976 section 3.3)
+pub fn digest(zone: &str, records: &[ZoneRecord], hash_algorithm: u8) -> Result<Vec<u8>, Error> {
+    let algorithm = match hash_algorithm {
+        SHA384 => &digest::SHA384,
+        SHA512 => &digest::SHA512,
+        algorithm => return Err(Error::UnsupportedAlgorithm(algorithm)),
+    };
+    let apex = wire::labels(zone.as_bytes())?;
+    let mut rrs = Vec::with_capacity(records.len());
+    for record in records {
+        let labels = wire::labels(record.name.as_bytes())?;
+        if !wire::is_subdomain(&labels, &apex) {
+            return Err(Error::InvalidZone);
+        }
+        let at_apex = wire::names_equal(&labels, &apex);
+        let covered = record
+            .rdata
+            .get(..2)
+            .map(|t| u16::from_be_bytes([t[0], t[1]]));
+        if at_apex
+            && (record.rr_type == ZONEMD_TYPE
+                || record.rr_type == RRSIG && covered == Some(ZONEMD_TYPE))
+        {
+            continue;
+        }
+        rrs.push((labels, record, record.canonical_rdata()?));
+    }
+    rrs.sort_by(|a, b| {
+        wire::canonical_cmp(&a.0, &b.0)
+            .then(a.1.rr_type.cmp(&b.1.rr_type))
+            .then_with(|| a.2.cmp(&b.2))
+    });
+    rrs.dedup_by(|a, b| wire::names_equal(&a.0, &b.0) && a.1.rr_type == b.1.rr_type && a.2 == b.2);
+
+    let mut context = digest::Context::new(algorithm);
+    for (labels, record, rdata) in rrs {
+        let len = u16::try_from(rdata.len()).map_err(|_| Error::ParseError)?;
+        let mut rr = wire::canonical_name(&wire::name_to_string(&labels))?;
+        wire::push_u16(&mut rr, record.rr_type);
+        wire::push_u16(&mut rr, record.class as u16);
+        wire::push_u32(&mut rr, record.ttl);
+        wire::push_u16(&mut rr, len);
+        rr.extend_from_slice(&rdata);
+        context.update(&rr);
+    }
+    Ok(context.finish().as_ref().to_vec())
+}
+
+/// A ZONEMD record for `records`, the records of `zone`, with the SOA's serial and TTL
+pub fn zonemd(zone: &str, records: &[ZoneRecord], hash_algorithm: u8) -> Result<ZoneRecord, Error> {
+    let soa = apex_soa(zone, records)?;
+    let zonemd = ZONEMD {
+        serial: soa.to_record::<SOA>()?.data.serial,
+        scheme: SIMPLE,
+        hash_algorithm,
+        digest: digest(zone, records, hash_algorithm)?,
+    };
+    Ok(ZoneRecord {
+        name: soa.name.clone(),
+        class: soa.class,
+        ttl: soa.ttl,
+        rr_type: ZONEMD_TYPE,
+        rdata: zonemd.to_rdata(),
+    })
+}
+
+/// Check `records`, the records of `zone`, against the zone's ZONEMD records (RFC 8976
+/// section 4).  One ZONEMD record for the SOA serial with a supported scheme and
+/// algorithm must match.  Fails with `Error::UnsupportedAlgorithm` if there are ZONEMD
+/// records for the serial but none is supported, and with `Error::BadZoneDigest` if
+/// there are none, none matches, or two have the same scheme and algorithm.
+pub fn verify(zone: &str, records: &[ZoneRecord]) -> Result<(), Error> {
+    let serial = apex_soa(zone, records)?.to_record::<SOA>()?.data.serial;
+    let apex = wire::labels(zone.as_bytes())?;
+    let mut zonemds = Vec::new();
+    for record in records.iter().filter(|r| r.rr_type == ZONEMD_TYPE) {
+        if wire::names_equal(&wire::labels(record.name.as_bytes())?, &apex) {
+            zonemds.push(record.to_record::<ZONEMD>()?.data);
+        }
+    }
+    zonemds.retain(|z| z.serial == serial);
+    let first = zonemds.first().ok_or(Error::BadZoneDigest)?.hash_algorithm;
+    zonemds.retain(|z| z.scheme == SIMPLE && [SHA384, SHA512].contains(&z.hash_algorithm));
+    if zonemds.is_empty() {
+        return Err(Error::UnsupportedAlgorithm(first));
+    }
+    for (i, zonemd) in zonemds.iter().enumerate() {
+        if zonemds[..i]
+            .iter()
+            .any(|z| z.hash_algorithm == zonemd.hash_algorithm)
+        {
+            return Err(Error::BadZoneDigest);
+        }
+    }
+    for zonemd in &zonemds {
+        if digest(zone, records, zonemd.hash_algorithm)? == zonemd.digest {
+            return Ok(());
+        }
+    }
+    Err(Error::BadZoneDigest)
+}
+
+fn apex_soa<'a>(zone: &str, records: &'a [ZoneRecord]) -> Result<&'a ZoneRecord, Error> {
+    let apex = wire::labels(zone.as_bytes())?;
+    records
+        .iter()
+        .find(|r| {
+            r.rr_type == RecordType::SOA as u16
+                && wire::labels(r.name.as_bytes()).is_ok_and(|l| wire::names_equal(&l, &apex))
+        })
+        .ok_or(Error::InvalidZone)
+}